@@ -1,22 +1,39 @@
 use std::{
     fs,
     io::{BufRead, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{bail, Context, Result};
-use borderless::{common::IntroductionDto, BorderlessId};
-use cliclack::{
-    log::{info, warning},
-    select,
+use borderless::{
+    common::{Id, IntroductionDto},
+    BorderlessId,
+};
+use borderless_hash::Hash256;
+use borderless_pkg::{PkgType, WasmPkg};
+use clap::ValueEnum;
+use cliclack::select;
+use once_cell::sync::OnceCell;
+use reqwest::{
+    blocking::{Client, Request, RequestBuilder, Response},
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    Certificate, Identity, NoProxy, Proxy, StatusCode,
 };
-use reqwest::header::CONTENT_TYPE;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::fmt;
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+};
 use url::Url;
 
-use crate::config;
+use crate::{
+    config,
+    error::CliError,
+    log::{info, warning},
+    util::ensure_interactive,
+};
 
 // NOTE: We have to greatly expand this,
 // because a link should also consist of information about the certificate,
@@ -28,12 +45,143 @@ pub struct Link {
     pub name: String,
     pub api: Url,
     pub api_key: Option<String>,
+
+    /// PEM-encoded CA certificate(s) to verify the node with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// PEM-encoded client certificate for nodes that require mTLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+
+    /// PEM-encoded private key that belongs to the `client_cert`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+
+    /// Environment of the node ( unlabeled, if not set )
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<Environment>,
+
+    /// Kind of the linked service ( links without a kind are nodes )
+    #[serde(default, skip_serializing_if = "LinkKind::is_node")]
+    pub kind: LinkKind,
+
+    /// Version of the node API, that the node reported ( see [`Node::probe_api_version`] )
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+}
+
+/// Kind of the service behind a link
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    #[default]
+    Node,
+    Registry,
+}
+
+impl LinkKind {
+    fn is_node(&self) -> bool {
+        *self == LinkKind::Node
+    }
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkKind::Node => write!(f, "node"),
+            LinkKind::Registry => write!(f, "registry"),
+        }
+    }
 }
 
 impl fmt::Display for Link {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} - {}", self.name, self.api)
+        write!(f, "{} - {}", self.name, self.api)?;
+        if let Some(env) = &self.environment {
+            write!(f, " [{env}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Environment label of a linked node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Dev,
+    Staging,
+    Prod,
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Environment::Dev => write!(f, "dev"),
+            Environment::Staging => write!(f, "staging"),
+            Environment::Prod => write!(f, "prod"),
+        }
+    }
+}
+
+/// Name of the file that contains the links ( inside the profile directory )
+pub const LINK_DB_FILE: &str = "LINKS";
+
+/// Directory ( inside the profile directory ), that contains the cached peers of all nodes
+pub const PEER_CACHE_DIR: &str = "peers";
+
+/// Cached peers older than this are fetched again
+pub const PEER_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Version of the node API, that is used if nothing else is specified
+pub const DEFAULT_API_VERSION: &str = "v0";
+
+/// Unversioned endpoint, that reports the version of the node API
+pub const VERSION_ENDPOINT: &str = "/version";
+
+static API_OPTIONS: OnceCell<ApiOptions> = OnceCell::new();
+
+/// Options for all requests against nodes ( set via commandline )
+#[derive(Debug, Default)]
+pub struct ApiOptions {
+    /// Overrides the version of the node API
+    pub api_version: Option<String>,
+    /// Prints every request before it is sent
+    pub dump_requests: bool,
+}
+
+/// Registers the global [`ApiOptions`]
+pub fn init_api_options(options: ApiOptions) -> Result<()> {
+    if let Some(version) = &options.api_version {
+        validate_api_version(version)?;
+    }
+    API_OPTIONS.set(options).expect("api-options are unset");
+    Ok(())
+}
+
+fn api_options() -> &'static ApiOptions {
+    API_OPTIONS.get_or_init(ApiOptions::default)
+}
+
+/// Returns the version of the node API, that is used for links without a negotiated version
+pub fn api_version() -> &'static str {
+    api_options()
+        .api_version
+        .as_deref()
+        .unwrap_or(DEFAULT_API_VERSION)
+}
+
+/// Checks that the api-version has the form `v<NUMBER>`
+fn validate_api_version(version: &str) -> Result<()> {
+    let valid = version
+        .strip_prefix('v')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    if !valid {
+        bail!(CliError::usage(format!(
+            "invalid api-version '{version}' - expected something like 'v0' or 'v1'"
+        )));
     }
+    Ok(())
 }
 
 // NOTE: This is a very naive and easy implementation,
@@ -49,24 +197,20 @@ pub struct LinkDb {
 impl LinkDb {
     /// Opens the `LinkDb` and parses all its content
     pub fn open() -> Result<Self> {
-        let data_home = config::get_config().data_dir()?;
-        let db = data_home.join("LINKS");
+        let profile_dir = config::create_profile_dir()?;
+        Self::open_file(profile_dir.join(LINK_DB_FILE))
+    }
+
+    fn open_file(db: PathBuf) -> Result<Self> {
         if !db.exists() {
             fs::File::create(&db)?;
         } else if !db.is_file() {
             bail!("link-file '{}' must be a file", db.display());
         }
-        // Read file line by line
-        let content = fs::read(&db)?;
-        let mut links = Vec::new();
-        for line in content.lines() {
-            let link = serde_json::from_str(&line?).context(format!(
-                "corrupted data - consider removing '{}'",
-                db.display()
-            ))?;
-            links.push(link);
-        }
-
+        let links = read_links(&db).context(format!(
+            "corrupted data - consider removing '{}'",
+            db.display()
+        ))?;
         Ok(Self { db, links })
     }
 
@@ -75,6 +219,11 @@ impl LinkDb {
         self.links.clone()
     }
 
+    /// Returns the link with the given name
+    pub fn get_link(&self, name: &str) -> Option<&Link> {
+        self.links.iter().find(|l| l.name == name)
+    }
+
     /// Returns true if a link with the given name already exists
     pub fn contains(&self, name: &str) -> bool {
         self.links.iter().any(|l| l.name == name)
@@ -105,113 +254,1242 @@ impl LinkDb {
         self.links.push(new_link);
     }
 
+    /// Imports a link and resolves a name collision with the given strategy
+    pub fn import_link(&mut self, link: Link, strategy: ConflictStrategy) -> Result<Imported> {
+        let Some(existing) = self.get_link(&link.name) else {
+            self.add_link(link);
+            return Ok(Imported::Added);
+        };
+        if *existing == link {
+            return Ok(Imported::Unchanged);
+        }
+        match strategy {
+            ConflictStrategy::Skip => Ok(Imported::Skipped),
+            ConflictStrategy::Overwrite => {
+                self.modify_link(&link.name.clone(), link)?;
+                Ok(Imported::Overwritten)
+            }
+            ConflictStrategy::Rename => {
+                let name = self.unused_name(&link.name);
+                self.add_link(Link {
+                    name: name.clone(),
+                    ..link
+                });
+                Ok(Imported::Renamed(name))
+            }
+        }
+    }
+
+    /// Appends a numeric suffix to the name, until no link with that name exists
+    fn unused_name(&self, name: &str) -> String {
+        (2..)
+            .map(|n| format!("{name}-{n}"))
+            .find(|candidate| !self.contains(candidate))
+            .expect("there is always an unused name")
+    }
+
     /// Commits the links to disk
     pub fn commit(self) -> Result<()> {
         let mut file = fs::File::create(self.db)?;
         for link in self.links {
             let encoded = serde_json::to_string(&link)?;
-            let _ = file.write(encoded.as_bytes())?;
-            let _ = file.write("\n".as_bytes())?;
+            file.write_all(encoded.as_bytes())?;
+            file.write_all(b"\n")?;
         }
         file.flush()?;
         Ok(())
     }
 }
 
+/// Reads links from a file in the format of the link-db ( one json encoded link per line )
+pub fn read_links(path: &Path) -> Result<Vec<Link>> {
+    let content = fs::read(path).with_context(|| format!("failed to read '{}'", path.display()))?;
+    let mut links = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line?;
+        // Blank lines are left behind by manual edits of the file
+        if line.trim().is_empty() {
+            continue;
+        }
+        let link = serde_json::from_str(&line)
+            .with_context(|| format!("invalid link in line {}", idx + 1))?;
+        links.push(link);
+    }
+    Ok(links)
+}
+
+/// How to resolve a name collision, when links are imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConflictStrategy {
+    /// Keep the existing link
+    Skip,
+    /// Replace the existing link
+    Overwrite,
+    /// Import the link under a new name ( with a numeric suffix )
+    Rename,
+}
+
+/// Result of [`LinkDb::import_link`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Imported {
+    Added,
+    /// An identical link already exists
+    Unchanged,
+    Skipped,
+    Overwritten,
+    Renamed(String),
+}
+
+impl Link {
+    /// Returns the version of the node API, that is used for requests against this link
+    ///
+    /// `--api-version` takes precedence over the version, that was negotiated with the node.
+    pub fn effective_api_version(&self) -> &str {
+        api_options()
+            .api_version
+            .as_deref()
+            .or(self.api_version.as_deref())
+            .unwrap_or(DEFAULT_API_VERSION)
+    }
+
+    /// Compares two links field by field
+    ///
+    /// Returns the name of every differing field together with both values. API-keys are never shown.
+    pub fn differences(&self, other: &Link) -> Result<Vec<(String, String, String)>> {
+        let describe = |link: &Link| -> Result<serde_json::Map<String, Value>> {
+            let mut link = link.clone();
+            link.api_key = link.api_key.map(|_| "<SECRET_API_KEY>".to_string());
+            match serde_json::to_value(link)? {
+                Value::Object(map) => Ok(map),
+                _ => bail!("link is not encoded as object"),
+            }
+        };
+        let (ours, theirs) = (describe(self)?, describe(other)?);
+        let mut fields: Vec<&String> = ours.keys().chain(theirs.keys()).collect();
+        fields.sort();
+        fields.dedup();
+
+        let mut out = Vec::new();
+        for field in fields {
+            let (a, b) = (ours.get(field), theirs.get(field));
+            // The masked keys are equal, so compare the real ones
+            let differs = if field == "api_key" {
+                self.api_key != other.api_key
+            } else {
+                a != b
+            };
+            if differs {
+                let show = |v: Option<&Value>| v.map(Value::to_string).unwrap_or("-".to_string());
+                out.push((field.clone(), show(a), show(b)));
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Response of a node to a written introduction
+#[derive(Debug, Clone, Serialize)]
+pub struct WrittenIntroduction {
+    /// Id of the created contract or agent ( if the node returned one )
+    pub id: Option<Id>,
+    /// Raw response of the node
+    pub response: Value,
+}
+
+impl WrittenIntroduction {
+    fn from_response(response: Value) -> Self {
+        let id = serde_json::from_value(response.clone()).ok();
+        WrittenIntroduction { id, response }
+    }
+}
+
 pub struct Node {
     link: Link,
+    client: Client,
+    /// Version of the node API, that was negotiated again while the node was in use
+    renegotiated: RefCell<Option<String>>,
+    /// Set after the first renegotiation, so the version is probed at most once per node
+    probed: Cell<bool>,
 }
 
 impl Node {
-    #[allow(dead_code)]
-    pub fn new(link: Link) -> Self {
-        Node { link }
+    pub fn new(link: Link) -> Result<Self> {
+        let client = build_client(&link, config::get_config().proxy.as_ref(), None)?;
+        Ok(Node::with_client(link, client))
+    }
+
+    /// Creates a node, whose requests fail after the given timeout
+    pub fn with_timeout(link: Link, timeout: Duration) -> Result<Self> {
+        let client = build_client(&link, config::get_config().proxy.as_ref(), Some(timeout))?;
+        Ok(Node::with_client(link, client))
+    }
+
+    fn with_client(link: Link, client: Client) -> Self {
+        Node {
+            link,
+            client,
+            renegotiated: RefCell::new(None),
+            probed: Cell::new(false),
+        }
     }
 
     pub fn select() -> Result<Self> {
         let db = LinkDb::open()?;
-        let selectable = db.get_links();
-        if selectable.is_empty() {
-            bail!("There are no nodes are linked to the cli-tool. Use 'borderless link' to create a new link");
-        } else if selectable.len() == 1 {
+        Node::select_from(node_links(db.get_links())?, LinkKind::Node)
+    }
+
+    /// Like [`Node::select`], but only offers links to registries
+    pub fn select_registry() -> Result<Self> {
+        let db = LinkDb::open()?;
+        Node::select_from(registry_links(db.get_links())?, LinkKind::Registry)
+    }
+
+    fn select_from(selectable: Vec<Link>, kind: LinkKind) -> Result<Self> {
+        if selectable.len() == 1 {
             let link = selectable.into_iter().next().unwrap();
-            info(format!("Use node {}", link))?;
-            return Ok(Node { link });
+            info(format!("Use {kind} {}", link))?;
+            return Node::new(link);
         }
-        let mut prompt = select("Select node:");
+        ensure_interactive(format!(
+            "{} {kind} links exist; use --profile with a profile that links a single {kind}",
+            selectable.len()
+        ))?;
+        let mut prompt = select(format!("Select {kind}:"));
         for item in selectable {
             prompt = prompt.item(item.clone(), item.name, item.api);
         }
         let selection = prompt.filter_mode().interact()?;
-        Ok(Node { link: selection })
+        Node::new(selection)
+    }
+
+    /// Returns the link of the node
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    /// Returns the version of the node API, that is used for all requests
+    pub fn api_version(&self) -> String {
+        match &*self.renegotiated.borrow() {
+            Some(version) => version.clone(),
+            None => self.link.effective_api_version().to_string(),
+        }
+    }
+
+    /// Builds the url for an endpoint of the versioned node API
+    ///
+    /// The `path` must not contain the version prefix ( e.g. `node/info` instead of `/v0/node/info` ).
+    fn endpoint(&self, path: &str) -> Result<Url> {
+        let endpoint = format!("/{}/{path}", self.api_version());
+        Ok(self.link.api.join(&endpoint)?)
+    }
+
+    /// Sends a request to the node ( and dumps it, if requested )
+    ///
+    /// If the node does not know the endpoint, it may have been upgraded to another version of the API.
+    /// In that case the version is negotiated again and the request is repeated with the new version.
+    /// Endpoints, that answer with `404` as a regular result, use [`Node::send_exact`] instead.
+    fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let retry = request.try_clone();
+        let old_version = self.api_version();
+        let res = self.execute(request.build()?)?;
+        if res.status() != StatusCode::NOT_FOUND {
+            return Ok(res);
+        }
+        let Some(retry) = retry else {
+            return Ok(res);
+        };
+        let Some(new_version) = self.renegotiate(&old_version)? else {
+            return Ok(res);
+        };
+        let mut request = retry.build()?;
+        match with_api_version(request.url(), &old_version, &new_version) {
+            Some(url) => *request.url_mut() = url,
+            None => return Ok(res),
+        }
+        self.execute(request)
+    }
+
+    /// Like [`Node::send`], but a `404` is returned as is, without negotiating the version again
+    fn send_exact(&self, request: RequestBuilder) -> Result<Response> {
+        self.execute(request.build()?)
+    }
+
+    /// Executes a request without any negotiation
+    fn execute(&self, request: Request) -> Result<Response> {
+        if api_options().dump_requests {
+            info(describe_request(&request, self.link.api_key.as_deref()))?;
+        }
+        Ok(self.client.execute(request)?)
+    }
+
+    /// Asks the node for the version of its API
+    ///
+    /// Returns `None`, if the node does not offer the [`VERSION_ENDPOINT`] ( e.g. because it predates it ).
+    pub fn probe_api_version(&self) -> Result<Option<String>> {
+        let url = self.link.api.join(VERSION_ENDPOINT)?;
+        let res = self.execute(self.client.get(url).build()?)?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !res.status().is_success() {
+            bail!(CliError::network(format!(
+                "failed to query the api-version - node responded with {}",
+                res.status()
+            )));
+        }
+        let body: Value =
+            serde_json::from_slice(&res.bytes()?).context("failed to parse api-version of node")?;
+        parse_api_version(&body).map(Some)
+    }
+
+    /// Negotiates the version of the node API again, after a request with `old_version` failed
+    ///
+    /// Returns the new version, if the node reports a different one. The new version is stored in the link-db.
+    /// Nothing is negotiated, if the version was given via `--api-version` or the node was already probed.
+    fn renegotiate(&self, old_version: &str) -> Result<Option<String>> {
+        if api_options().api_version.is_some() || self.probed.replace(true) {
+            return Ok(None);
+        }
+        let new_version = match self.probe_api_version() {
+            Ok(Some(version)) if version != old_version => version,
+            // A node without version endpoint or an unchanged version is not a version mismatch
+            Ok(_) => return Ok(None),
+            Err(e) => {
+                warning(format!(
+                    "Failed to negotiate the api-version of '{}': {e:#}",
+                    self.link.name
+                ))?;
+                return Ok(None);
+            }
+        };
+        warning(format!(
+            "Node '{}' uses api-version {new_version} instead of {old_version} - updating the link",
+            self.link.name
+        ))?;
+        *self.renegotiated.borrow_mut() = Some(new_version.clone());
+        if let Err(e) = store_api_version(&self.link.name, &new_version) {
+            warning(format!("Failed to update the link: {e:#}"))?;
+        }
+        Ok(Some(new_version))
     }
 
     /// Writes an introduction
-    pub fn write_introduction(&self, introduction: IntroductionDto) -> Result<bool> {
-        let endpoint = "/v0/write/introduction";
-        let url = self.link.api.join(endpoint)?;
+    ///
+    /// With `upgrade`, the introduction replaces an existing contract or agent with the same id.
+    /// Returns the response of the node, or `None` if the node rejected the introduction.
+    /// Conflicts with existing contracts or agents are reported as error.
+    pub fn write_introduction(
+        &self,
+        introduction: IntroductionDto,
+        upgrade: bool,
+    ) -> Result<Option<WrittenIntroduction>> {
+        let mut url = self.endpoint("write/introduction")?;
+        if upgrade {
+            url.query_pairs_mut().append_pair("upgrade", "true");
+        }
 
         let body = serde_json::to_vec(&introduction)?;
 
-        let client = reqwest::blocking::Client::new();
-        let res = client
-            .post(url)
-            .header(CONTENT_TYPE, "application/json")
-            .body(body)
-            .send()?;
+        let res = self.send(
+            self.client
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body),
+        )?;
 
+        if res.status() == StatusCode::CONFLICT {
+            let reason = res.text().unwrap_or_default();
+            bail!(CliError::network(describe_conflict(upgrade, &reason)));
+        }
+        if !res.status().is_success() {
+            return Ok(None);
+        }
+
+        let body = res.bytes()?;
+        let response: Value = serde_json::from_slice(&body)?;
+        Ok(Some(WrittenIntroduction::from_response(response)))
+    }
+
+    /// Returns the status of an introduced contract or agent
+    ///
+    /// Returns `None`, if the node does not know the id (yet).
+    pub fn introduction_status(&self, id: &Id) -> Result<Option<String>> {
+        let url = self.endpoint(&format!("status/{id}"))?;
+
+        // Unknown ids are answered with 404 while the introduction is processed
+        let res = self.send_exact(self.client.get(url))?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
         if !res.status().is_success() {
-            return Ok(false);
+            bail!(CliError::network(format!(
+                "failed to query status of {id} - node responded with {}",
+                res.status()
+            )));
         }
 
         let body = res.bytes()?;
         let value: Value = serde_json::from_slice(&body)?;
 
-        let pretty = serde_json::to_string_pretty(&value)?;
-        info(pretty)?;
+        // The status is either a plain string or an object with a "status" field
+        let status = match &value {
+            Value::String(s) => s.clone(),
+            other => other
+                .get("status")
+                .and_then(|s| s.as_str())
+                .context("missing field 'status' in status response")?
+                .to_string(),
+        };
+        Ok(Some(status))
+    }
+
+    /// Returns all contracts and agents, that are deployed on the node
+    pub fn deployed_packages(&self) -> Result<Vec<DeployedPkg>> {
+        let mut out = Vec::new();
+        for (pkg_type, path, id_field) in [
+            (PkgType::Contract, "contracts", "contract_id"),
+            (PkgType::Agent, "agents", "agent_id"),
+        ] {
+            let url = self.endpoint(path)?;
+            // A version mismatch would look like an empty node, so this still negotiates ( at most once )
+            let res = self.send(self.client.get(url))?;
+            // Not every node hosts agents ( or contracts )
+            if res.status() == StatusCode::NOT_FOUND {
+                continue;
+            }
+            if !res.status().is_success() {
+                bail!(CliError::network(format!(
+                    "failed to list {path} - node responded with {}",
+                    res.status()
+                )));
+            }
+            let body = res.bytes()?;
 
-        Ok(true)
+            // Again, we only pick the fields we are interested in
+            let entries: Vec<Value> = serde_json::from_slice(&body)?;
+            for entry in entries {
+                let field =
+                    |name: &str| entry.get(name).and_then(|v| v.as_str()).map(str::to_string);
+                out.push(DeployedPkg {
+                    id: field(id_field).unwrap_or_default(),
+                    pkg_type: pkg_type.clone(),
+                    name: field("name"),
+                    version: field("version"),
+                    state_hash: field("state_hash"),
+                });
+            }
+        }
+        Ok(out)
     }
 
     /// Returns the node-info
     pub fn node_info(&self) -> Result<Value> {
-        let endpoint = "/v0/node/info";
-        let url = self.link.api.join(endpoint)?;
+        let url = self.endpoint("node/info")?;
 
-        let result = reqwest::blocking::get(url)?;
+        let result = self.send(self.client.get(url))?;
         let body = result.bytes()?;
 
         let info: Value = serde_json::from_slice(&body)?;
         Ok(info)
     }
 
+    /// Returns all packages, that are published in a registry
+    pub fn registry_packages(&self) -> Result<Vec<RegistryPkg>> {
+        let url = self.endpoint("registry/packages")?;
+        let res = self.send(self.client.get(url))?;
+        if !res.status().is_success() {
+            bail!(CliError::network(format!(
+                "failed to list packages - registry responded with {}",
+                res.status()
+            )));
+        }
+        let body = res.bytes()?;
+        let packages: Vec<RegistryPkg> =
+            serde_json::from_slice(&body).context("failed to parse package list of registry")?;
+        Ok(packages)
+    }
+
+    /// Downloads a single package from a registry
+    pub fn registry_package(&self, name: &str, version: &str) -> Result<WasmPkg> {
+        let url = self.endpoint(&format!("registry/packages/{name}/{version}"))?;
+        let res = self.send_exact(self.client.get(url))?;
+        if res.status() == StatusCode::NOT_FOUND {
+            bail!(CliError::usage(format!(
+                "package '{name}@{version}' does not exist in '{}'",
+                self.link.name
+            )));
+        }
+        if !res.status().is_success() {
+            bail!(CliError::network(format!(
+                "failed to download '{name}@{version}' - registry responded with {}",
+                res.status()
+            )));
+        }
+        let body = res.bytes()?;
+        let package: WasmPkg = serde_json::from_slice(&body)
+            .with_context(|| format!("failed to parse package '{name}@{version}'"))?;
+        Ok(package)
+    }
+
+    /// Like [`Node::network_peers`], but reuses the peers of a previous request if `use_cache` is set
+    ///
+    /// Cached peers expire after [`PEER_CACHE_TTL`]. Freshly fetched peers are always written to the cache.
+    pub fn network_peers_cached(
+        &self,
+        filter: Option<&str>,
+        use_cache: bool,
+    ) -> Result<Vec<(String, BorderlessId)>> {
+        let cache_file = PeerCache::file(&self.link)?;
+        let cached = use_cache
+            .then(|| PeerCache::load(&cache_file, PEER_CACHE_TTL))
+            .flatten();
+        let mut peers = match cached {
+            Some(cache) => {
+                info(format!("Using cached peers of '{}'", self.link.name))?;
+                cache.peers
+            }
+            None => {
+                let peers = self.network_peers(None)?;
+                if let Err(e) = PeerCache::new(peers.clone()).store(&cache_file) {
+                    warning(format!("Failed to cache peers: {e:#}"))?;
+                }
+                peers
+            }
+        };
+        if let Some(filter) = filter {
+            peers.retain(|(name, pid)| matches_filter(filter, name, pid));
+        }
+        Ok(peers)
+    }
+
     /// Returns the list of network peers for a node
-    pub fn network_peers(&self) -> Result<Vec<(String, BorderlessId)>> {
-        let endpoint = "/v0/node/cert?node_type=contract";
-        let url = self.link.api.join(endpoint)?;
+    ///
+    /// If a filter is given, only peers whose name or id contains the filter ( ignoring case ) are returned.
+    pub fn network_peers(&self, filter: Option<&str>) -> Result<Vec<(String, BorderlessId)>> {
+        let url = self.endpoint("node/cert?node_type=contract")?;
 
-        let result = reqwest::blocking::get(url)?;
+        let result = self.send(self.client.get(url))?;
         let body = result.bytes()?;
 
         // We don't use the real model here, we just now it's a list of something
         let certs: Vec<Value> = serde_json::from_slice(&body)?;
+        let mut out = parse_peers(&certs)?;
 
-        let mut out = Vec::new();
+        if let Some(filter) = filter {
+            out.retain(|(name, pid)| matches_filter(filter, name, pid));
+        }
+        Ok(out)
+    }
+}
+
+/// Extracts the api-version from the response of the [`VERSION_ENDPOINT`]
+fn parse_api_version(body: &Value) -> Result<String> {
+    let Some(version) = body.get("api_version").and_then(Value::as_str) else {
+        bail!(CliError::network("node returned no field 'api_version'"));
+    };
+    if validate_api_version(version).is_err() {
+        bail!(CliError::network(format!(
+            "node returned an invalid api-version '{version}'"
+        )));
+    }
+    Ok(version.to_string())
+}
+
+/// Replaces the version prefix of a versioned url
+///
+/// Returns `None`, if the url does not start with the given version.
+fn with_api_version(url: &Url, old: &str, new: &str) -> Option<Url> {
+    let rest = url.path().strip_prefix(&format!("/{old}/"))?;
+    let mut url = url.clone();
+    url.set_path(&format!("/{new}/{rest}"));
+    Some(url)
+}
+
+/// Stores the negotiated api-version of a link in the link-db
+fn store_api_version(name: &str, version: &str) -> Result<()> {
+    let mut db = LinkDb::open()?;
+    let Some(link) = db.get_link(name).cloned() else {
+        return Ok(());
+    };
+    db.modify_link(
+        name,
+        Link {
+            api_version: Some(version.to_string()),
+            ..link
+        },
+    )?;
+    db.commit()
+}
+
+/// Extracts the name and id of every peer from the certificates of a node
+///
+/// A malformed id is reported together with the subject of its certificate, so the user knows which peer is affected.
+fn parse_peers(certs: &[Value]) -> Result<Vec<(String, BorderlessId)>> {
+    let mut out = Vec::new();
+    for (idx, cert) in certs.iter().enumerate() {
+        let name = cert
+            .get("subject")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let peer = if name.is_empty() {
+            format!("certificate #{}", idx + 1)
+        } else {
+            format!("peer '{name}'")
+        };
+        let Some(raw_id) = cert.get("participant_id").and_then(Value::as_str) else {
+            bail!(CliError::network(format!(
+                "node returned no participant id for {peer}"
+            )));
+        };
+        let pid: BorderlessId = raw_id.parse().map_err(|e| {
+            CliError::network(format!(
+                "node returned an invalid participant id '{raw_id}' for {peer}: {e}"
+            ))
+        })?;
+        out.push((name, pid));
+    }
+    Ok(out)
+}
+
+/// Explains why the node refused an introduction with `409 Conflict`
+fn describe_conflict(upgrade: bool, reason: &str) -> String {
+    // Nodes answer with a json error object or with plain text
+    let reason = serde_json::from_str::<Value>(reason)
+        .ok()
+        .and_then(|v| v.get("error").and_then(Value::as_str).map(str::to_string))
+        .unwrap_or_else(|| reason.trim().to_string());
+    let reason = if reason.is_empty() {
+        String::new()
+    } else {
+        format!(" ( {reason} )")
+    };
+    if upgrade {
+        format!(
+            "node rejected the upgrade{reason} - the version may conflict with the deployed one"
+        )
+    } else {
+        format!(
+            "contract or agent already exists on the node{reason} - use --upgrade to replace it"
+        )
+    }
+}
+
+/// Returns the links, that point to nodes
+///
+/// Fails, if there are no links at all or only links to registries.
+fn node_links(links: Vec<Link>) -> Result<Vec<Link>> {
+    if links.is_empty() {
+        bail!(
+            "There are no nodes linked to the cli-tool. Use 'borderless link' to create a new link"
+        );
+    }
+    let nodes: Vec<_> = links
+        .into_iter()
+        .filter(|link| link.kind == LinkKind::Node)
+        .collect();
+    if nodes.is_empty() {
+        bail!("There are no nodes linked to the cli-tool, only registries. Use 'borderless link' to create a new link of kind 'node'");
+    }
+    Ok(nodes)
+}
+
+/// Returns the links, that point to registries
+fn registry_links(links: Vec<Link>) -> Result<Vec<Link>> {
+    let registries: Vec<_> = links
+        .into_iter()
+        .filter(|link| link.kind == LinkKind::Registry)
+        .collect();
+    if registries.is_empty() {
+        bail!("There are no registries linked to the cli-tool. Use 'borderless link' to create a new link of kind 'registry'");
+    }
+    Ok(registries)
+}
+
+/// Peers of a node, that are stored on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct PeerCache {
+    /// Unix timestamp ( in seconds ) of the request
+    fetched_at: u64,
+    peers: Vec<(String, BorderlessId)>,
+}
+
+impl PeerCache {
+    fn new(peers: Vec<(String, BorderlessId)>) -> Self {
+        PeerCache {
+            fetched_at: unix_time(),
+            peers,
+        }
+    }
+
+    /// Returns the cache file of a link
+    fn file(link: &Link) -> Result<PathBuf> {
+        let dir = config::get_config().profile_dir()?.join(PEER_CACHE_DIR);
+        Ok(dir.join(Self::file_name(link)))
+    }
+
+    /// Name of the cache file of a link
+    ///
+    /// The file is keyed on the name and the api of the link, so names that only differ
+    /// in special characters don't share a cache, and pointing a link to another node
+    /// starts with an empty one.
+    fn file_name(link: &Link) -> String {
+        let readable: String = link
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let key = Hash256::digest(&format!("{}\n{}", link.name, link.api));
+        let mut key = String::from(key);
+        key.truncate(16);
+        format!("{readable}-{key}.json")
+    }
+
+    /// Loads the cache, unless it is missing, unreadable or older than `ttl`
+    fn load(path: &Path, ttl: Duration) -> Option<Self> {
+        let cache: PeerCache = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+        let age = unix_time().saturating_sub(cache.fetched_at);
+        (age < ttl.as_secs()).then_some(cache)
+    }
+
+    fn store(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec(self)?)
+            .with_context(|| format!("failed to write '{}'", path.display()))
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A contract or agent, that is deployed on a node
+#[derive(Debug, Clone)]
+pub struct DeployedPkg {
+    pub id: String,
+    pub pkg_type: PkgType,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub state_hash: Option<String>,
+}
+
+/// A package, that is published in a registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryPkg {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+/// Headers, whose values are never printed
+/// Header that carries the api key of a link
+const API_KEY_HEADER: &str = "x-api-key";
+
+const SECRET_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+];
+
+/// Describes a request for debugging purposes
+///
+/// Secret headers and any header that contains the api-key are masked.
+fn describe_request(request: &Request, api_key: Option<&str>) -> String {
+    let mut out = format!("{} {}", request.method(), request.url());
+    for (name, value) in request.headers() {
+        let value = value.to_str().unwrap_or("<binary>");
+        let is_secret = SECRET_HEADERS.contains(&name.as_str())
+            || api_key.is_some_and(|key| !key.is_empty() && value.contains(key));
+        let value = if is_secret { "****" } else { value };
+        out.push_str(&format!("\n{name}: {value}"));
+    }
+    match request.body() {
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => out.push_str(&format!("\nbody: {} bytes", bytes.len())),
+            None => out.push_str("\nbody: <stream>"),
+        },
+        None => out.push_str("\nbody: <empty>"),
+    }
+    out
+}
+
+fn matches_filter(filter: &str, name: &str, pid: &BorderlessId) -> bool {
+    let filter = filter.to_lowercase();
+    name.to_lowercase().contains(&filter) || pid.to_string().to_lowercase().contains(&filter)
+}
+
+/// Builds the http-client that is used for all requests against the linked node
+///
+/// Proxy settings are applied with the following precedence:
+/// 1. The `proxy` field of the config
+/// 2. The `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` environment variables
+///
+/// Hosts that are listed in `NO_PROXY` always bypass the proxy.
+///
+/// If the link specifies a CA certificate or a client certificate, they are added to the client.
+fn build_client(link: &Link, proxy: Option<&Url>, timeout: Option<Duration>) -> Result<Client> {
+    let mut builder = Client::builder();
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(proxy) = proxy {
+        let proxy = Proxy::all(proxy.as_str())
+            .with_context(|| format!("invalid proxy url '{proxy}'"))?
+            .no_proxy(NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(ca_cert) = &link.ca_cert {
+        let pem = fs::read(ca_cert)
+            .with_context(|| format!("failed to read CA certificate '{}'", ca_cert.display()))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("invalid CA certificate '{}'", ca_cert.display()))?;
         for cert in certs {
-            let pid: BorderlessId = cert
-                .get("participant_id")
-                .and_then(|s| s.as_str())
-                .unwrap_or_default()
-                .parse()?;
+            builder = builder.add_root_certificate(cert);
+        }
+    }
 
-            let name: String = cert
-                .get("subject")
-                .and_then(|s| s.as_str())
-                .unwrap_or_default()
-                .to_string();
-            out.push((name, pid));
+    match (&link.client_cert, &link.client_key) {
+        (Some(cert), Some(key)) => {
+            // The identity expects the certificate and private key in one PEM buffer
+            let mut pem = fs::read(cert).with_context(|| {
+                format!("failed to read client certificate '{}'", cert.display())
+            })?;
+            pem.push(b'\n');
+            pem.extend(
+                fs::read(key)
+                    .with_context(|| format!("failed to read client key '{}'", key.display()))?,
+            );
+            let identity = Identity::from_pem(&pem).context("invalid client certificate or key")?;
+            builder = builder.identity(identity);
         }
+        (None, None) => (),
+        _ => bail!(CliError::usage(format!(
+            "link '{}' must specify both a client certificate and a client key",
+            link.name
+        ))),
+    }
 
-        Ok(out)
+    // Every request to the node is authenticated with the api key of the link
+    if let Some(api_key) = &link.api_key {
+        let mut value = HeaderValue::from_str(api_key)
+            .with_context(|| format!("invalid api key for link '{}'", link.name))?;
+        value.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, value);
+        builder = builder.default_headers(headers);
+    }
+
+    builder.build().context("failed to build http-client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::ErrorKind, util::TestDir};
+
+    #[test]
+    fn legacy_links_without_tls() -> Result<()> {
+        let link: Link = serde_json::from_str(
+            r#"{"name":"node","api":"http://localhost:3000/","api_key":null}"#,
+        )?;
+        assert!(link.ca_cert.is_none());
+        assert!(link.client_cert.is_none());
+        assert!(link.client_key.is_none());
+        assert!(link.environment.is_none());
+        // Unset tls fields are not written to disk
+        assert!(!serde_json::to_string(&link)?.contains("ca_cert"));
+        Ok(())
+    }
+
+    fn link(name: &str, api_key: Option<&str>) -> Link {
+        Link {
+            name: name.to_string(),
+            api: "http://localhost:3000".parse().unwrap(),
+            api_key: api_key.map(str::to_string),
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            environment: None,
+            kind: LinkKind::Node,
+            api_version: None,
+        }
+    }
+
+    #[test]
+    fn link_db_persistence() -> Result<()> {
+        let dir = TestDir::new("links");
+        let file = dir.join(LINK_DB_FILE);
+
+        // A missing file is created on open
+        let mut db = LinkDb::open_file(file.clone())?;
+        assert!(file.is_file());
+        assert!(db.get_links().is_empty());
+
+        db.add_link(link("alice", Some("secret-key")));
+        db.add_link(link("bob", None));
+        db.commit()?;
+
+        let mut db = LinkDb::open_file(file.clone())?;
+        assert_eq!(
+            db.get_links(),
+            [link("alice", Some("secret-key")), link("bob", None)]
+        );
+        assert!(db.contains("alice"));
+        assert!(!db.contains("carol"));
+
+        // Modified links are moved to the end, removing unknown links is a no-op
+        db.modify_link("alice", link("alice", None))?;
+        db.remove_link("carol")?;
+        db.remove_link("bob")?;
+        db.commit()?;
+        let db = LinkDb::open_file(file.clone())?;
+        assert_eq!(db.get_links(), [link("alice", None)]);
+
+        // Blank lines are ignored, but garbage is reported
+        let content = fs::read_to_string(&file)?;
+        fs::write(&file, format!("\n{content}\n  \n"))?;
+        assert_eq!(LinkDb::open_file(file.clone())?.get_links().len(), 1);
+        fs::write(&file, format!("{content}not json\n"))?;
+        let err = LinkDb::open_file(file.clone()).unwrap_err();
+        assert!(err.to_string().contains("corrupted data"));
+        Ok(())
+    }
+
+    #[test]
+    fn import_conflicts() -> Result<()> {
+        let dir = TestDir::new("import");
+        let mut db = LinkDb::open_file(dir.join(LINK_DB_FILE))?;
+        db.add_link(link("alice", Some("key")));
+        db.add_link(link("alice-2", None));
+
+        let other = link("alice", Some("other-key"));
+        let import =
+            |db: &mut LinkDb, link: &Link, strategy| db.import_link(link.clone(), strategy);
+        assert_eq!(
+            import(
+                &mut db,
+                &link("alice", Some("key")),
+                ConflictStrategy::Overwrite
+            )?,
+            Imported::Unchanged
+        );
+        assert_eq!(
+            import(&mut db, &other, ConflictStrategy::Skip)?,
+            Imported::Skipped
+        );
+        assert_eq!(
+            import(&mut db, &other, ConflictStrategy::Rename)?,
+            Imported::Renamed("alice-3".to_string())
+        );
+        assert_eq!(
+            import(&mut db, &other, ConflictStrategy::Overwrite)?,
+            Imported::Overwritten
+        );
+        assert_eq!(
+            import(&mut db, &link("bob", None), ConflictStrategy::Skip)?,
+            Imported::Added
+        );
+        let names: Vec<_> = db.get_links().into_iter().map(|l| l.name).collect();
+        assert_eq!(names, ["alice-2", "alice-3", "alice", "bob"]);
+        assert_eq!(db.get_link("alice"), Some(&other));
+        Ok(())
+    }
+
+    #[test]
+    fn link_differences() -> Result<()> {
+        let mut other = link("alice", Some("other-key"));
+        other.environment = Some(Environment::Prod);
+        let diff = link("alice", Some("key")).differences(&other)?;
+        let fields: Vec<_> = diff.iter().map(|(field, ..)| field.as_str()).collect();
+        assert_eq!(fields, ["api_key", "environment"]);
+        assert_eq!(diff[1].1, "-");
+        assert_eq!(diff[1].2, "\"prod\"");
+        // The keys themselves are never part of the output
+        assert!(!format!("{diff:?}").contains("other-key"));
+        Ok(())
+    }
+
+    #[test]
+    fn id_from_introduction_response() {
+        let cid = "cfc23cb3-f447-8107-8f93-9bfb8e1d157d";
+        let written = WrittenIntroduction::from_response(serde_json::json!({ "contract_id": cid }));
+        assert_eq!(written.id.unwrap().to_string(), cid);
+
+        let written = WrittenIntroduction::from_response(serde_json::json!({ "ok": true }));
+        assert!(written.id.is_none());
+    }
+
+    #[test]
+    fn conflict_messages() {
+        let msg = describe_conflict(false, r#"{"error":"duplicate contract-id"}"#);
+        assert!(msg.contains("( duplicate contract-id )"), "{msg}");
+        assert!(msg.contains("--upgrade"), "{msg}");
+        let msg = describe_conflict(true, "version 0.1.0 is older\n");
+        assert!(
+            msg.contains("rejected the upgrade ( version 0.1.0 is older )"),
+            "{msg}"
+        );
+        assert!(!describe_conflict(true, "").contains("( "));
+    }
+
+    #[test]
+    fn api_version_format() {
+        assert!(validate_api_version("v0").is_ok());
+        assert!(validate_api_version("v12").is_ok());
+        assert!(validate_api_version("v").is_err());
+        assert!(validate_api_version("1").is_err());
+        assert!(validate_api_version("v1/../admin").is_err());
+    }
+
+    #[test]
+    fn versioned_endpoints() -> Result<()> {
+        let link = Link {
+            name: "test".to_string(),
+            api: "http://localhost:3000".parse()?,
+            api_key: None,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            environment: None,
+            kind: LinkKind::Node,
+            api_version: None,
+        };
+        let node = Node::with_client(link.clone(), build_client(&link, None, None)?);
+        let url = node.endpoint("node/cert?node_type=contract")?;
+        assert_eq!(
+            url.as_str(),
+            "http://localhost:3000/v0/node/cert?node_type=contract"
+        );
+
+        // The negotiated version of the link is used, until it is negotiated again
+        let link = Link {
+            api_version: Some("v1".to_string()),
+            ..link
+        };
+        let node = Node::with_client(link.clone(), build_client(&link, None, None)?);
+        assert_eq!(node.endpoint("node/info")?.path(), "/v1/node/info");
+        *node.renegotiated.borrow_mut() = Some("v2".to_string());
+        assert_eq!(node.endpoint("node/info")?.path(), "/v2/node/info");
+        Ok(())
+    }
+
+    #[test]
+    fn api_key_is_sent() -> Result<()> {
+        use std::io::Read;
+        use std::net::TcpListener;
+
+        // Answers a single request and returns what was received
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let n = stream.read(&mut buf).unwrap();
+            let _ = stream.write_all(
+                b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            );
+            String::from_utf8_lossy(&buf[..n]).to_lowercase()
+        });
+
+        let link = Link {
+            api: format!("http://{addr}").parse()?,
+            ..link("node", Some("secret-key"))
+        };
+        let client = build_client(&link, None, None)?;
+        client.get(link.api.clone()).send()?;
+        let request = server.join().unwrap();
+        assert!(request.contains("x-api-key: secret-key\r\n"), "{request}");
+        Ok(())
+    }
+
+    #[test]
+    fn api_version_is_probed_once() -> Result<()> {
+        use std::io::Read;
+        use std::net::TcpListener;
+
+        // Answers every request with 404 and counts the requests
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let server = std::thread::spawn(move || {
+            let mut requests = 0;
+            listener.set_nonblocking(true).unwrap();
+            let deadline = std::time::Instant::now() + Duration::from_millis(500);
+            while std::time::Instant::now() < deadline {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    std::thread::sleep(Duration::from_millis(10));
+                    continue;
+                };
+                stream.set_nonblocking(false).unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                );
+                requests += 1;
+            }
+            requests
+        });
+
+        let link = Link {
+            api: format!("http://{addr}").parse()?,
+            ..link("node", None)
+        };
+        let node = Node::with_client(link.clone(), build_client(&link, None, None)?);
+        assert_eq!(node.renegotiate("v0")?, None);
+        assert_eq!(node.renegotiate("v0")?, None);
+        assert_eq!(server.join().unwrap(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn api_version_negotiation() -> Result<()> {
+        let version = parse_api_version(&serde_json::json!({ "api_version": "v1" }))?;
+        assert_eq!(version, "v1");
+        let err = parse_api_version(&serde_json::json!({ "api_version": "1.0" })).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid api-version '1.0'"),
+            "{err}"
+        );
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Network);
+        assert!(parse_api_version(&serde_json::json!({})).is_err());
+
+        let url: Url = "http://localhost:3000/v0/node/cert?node_type=contract".parse()?;
+        let upgraded = with_api_version(&url, "v0", "v1").unwrap();
+        assert_eq!(
+            upgraded.as_str(),
+            "http://localhost:3000/v1/node/cert?node_type=contract"
+        );
+        assert!(with_api_version(&url, "v1", "v2").is_none());
+
+        // Links without a negotiated version are written like before
+        let link = link("node", None);
+        assert!(!serde_json::to_string(&link)?.contains("api_version"));
+        Ok(())
+    }
+
+    #[test]
+    fn dumped_requests_mask_secrets() -> Result<()> {
+        let request = Client::new()
+            .post("http://localhost:3000/v0/write/introduction")
+            .header(CONTENT_TYPE, "application/json")
+            .header("authorization", "Bearer secret")
+            .header("x-custom", "key=secret")
+            .body("{}")
+            .build()?;
+        let dump = describe_request(&request, Some("secret"));
+        assert!(dump.starts_with("POST http://localhost:3000/v0/write/introduction"));
+        assert!(dump.contains("content-type: application/json"), "{dump}");
+        assert!(dump.contains("authorization: ****"), "{dump}");
+        assert!(dump.contains("x-custom: ****"), "{dump}");
+        assert!(dump.contains("body: 2 bytes"), "{dump}");
+        assert!(!dump.contains("secret"), "{dump}");
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_peer_ids() -> Result<()> {
+        let certs = serde_json::json!([
+            { "participant_id": "0bc23cb3-f447-8107-8f93-9bfb8e1d157d", "subject": "alice" },
+            { "participant_id": "not-an-id", "subject": "bob" },
+        ]);
+        let certs = certs.as_array().unwrap();
+        let peers = parse_peers(&certs[..1])?;
+        assert_eq!(peers[0].0, "alice");
+
+        let err = parse_peers(certs).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("invalid participant id 'not-an-id' for peer 'bob'"),
+            "{err}"
+        );
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Network);
+
+        let err = parse_peers(&[serde_json::json!({})]).unwrap_err();
+        assert!(err.to_string().contains("for certificate #1"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn select_links_by_kind() -> Result<()> {
+        let err = node_links(Vec::new()).unwrap_err().to_string();
+        assert!(err.contains("There are no nodes linked"), "{err}");
+
+        let registry = Link {
+            kind: LinkKind::Registry,
+            ..link("registry", None)
+        };
+        let err = node_links(vec![registry.clone()]).unwrap_err().to_string();
+        assert!(err.contains("only registries"), "{err}");
+
+        let nodes = node_links(vec![registry.clone(), link("node", None)])?;
+        assert_eq!(nodes, [link("node", None)]);
+
+        let err = registry_links(vec![link("node", None)]).unwrap_err();
+        assert!(err.to_string().contains("no registries linked"), "{err}");
+        let registries = registry_links(vec![registry.clone(), link("node", None)])?;
+        assert_eq!(registries, [registry]);
+
+        // Existing links are nodes
+        let link: Link = serde_json::from_str(r#"{"name":"n","api":"http://n/","api_key":null}"#)?;
+        assert_eq!(link.kind, LinkKind::Node);
+        Ok(())
+    }
+
+    #[test]
+    fn peer_cache_expiry() -> Result<()> {
+        let dir = TestDir::new("peers");
+        let file = dir.join(PEER_CACHE_DIR).join("node.json");
+        let pid: BorderlessId = "0bc23cb3-f447-8107-8f93-9bfb8e1d157d".parse()?;
+
+        assert!(PeerCache::load(&file, PEER_CACHE_TTL).is_none());
+        PeerCache::new(vec![("alice".to_string(), pid)]).store(&file)?;
+        let cache = PeerCache::load(&file, PEER_CACHE_TTL).unwrap();
+        assert_eq!(cache.peers, [("alice".to_string(), pid)]);
+        assert!(PeerCache::load(&file, Duration::ZERO).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn peer_cache_file_names() -> Result<()> {
+        let file_name = |name: &str, api: &str| -> Result<String> {
+            let link = Link {
+                api: api.parse()?,
+                ..link(name, None)
+            };
+            Ok(PeerCache::file_name(&link))
+        };
+        let dotted = file_name("a.b", "http://localhost:3000")?;
+        assert!(
+            dotted.starts_with("a_b-") && dotted.ends_with(".json"),
+            "{dotted}"
+        );
+        assert_ne!(dotted, file_name("a_b", "http://localhost:3000")?);
+        assert_ne!(dotted, file_name("a.b", "http://localhost:4000")?);
+        assert_eq!(dotted, file_name("a.b", "http://localhost:3000")?);
+        Ok(())
+    }
+
+    #[test]
+    fn peer_filter() -> Result<()> {
+        let pid: BorderlessId = "0bc23cb3-f447-8107-8f93-9bfb8e1d157d".parse()?;
+        assert!(matches_filter("ALI", "alice", &pid));
+        assert!(matches_filter("8e1d157", "alice", &pid));
+        assert!(!matches_filter("bob", "alice", &pid));
+        Ok(())
     }
 }
@@ -0,0 +1,161 @@
+//! Evaluation of the output of `cargo build`
+use serde_json::Value;
+use std::{path::PathBuf, time::Duration};
+
+/// A crate that was compiled during the build
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledUnit {
+    pub name: String,
+    /// Time between the previous artifact and this one
+    ///
+    /// Cargo builds crates in parallel, so this only approximates the compile time of the crate.
+    pub duration: Duration,
+}
+
+/// Diagnostics and artifacts extracted from the json messages of `cargo build --message-format=json`
+#[derive(Debug, Default)]
+pub struct CargoOutput {
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+    /// Wasm binaries of all `cdylib` targets, in the order they were built
+    pub wasm_artifacts: Vec<PathBuf>,
+    pub units: Vec<CompiledUnit>,
+    /// Time of the last reported artifact ( relative to the start of the build )
+    last_artifact: Duration,
+}
+
+impl CargoOutput {
+    /// Processes a single line of cargo's json output ( other lines are ignored )
+    ///
+    /// `elapsed` is the time since the start of the build, when the line was received.
+    pub fn process_line(&mut self, line: &str, elapsed: Duration) {
+        let Ok(msg) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+        match msg.get("reason").and_then(Value::as_str) {
+            Some("compiler-message") => self.process_message(&msg["message"]),
+            Some("compiler-artifact") => self.process_artifact(&msg, elapsed),
+            _ => (),
+        }
+    }
+
+    fn process_artifact(&mut self, msg: &Value, elapsed: Duration) {
+        // Fresh artifacts were reused from a previous build, so rustc was not invoked for them
+        if !msg["fresh"].as_bool().unwrap_or_default() {
+            self.units.push(CompiledUnit {
+                name: msg["target"]["name"].as_str().unwrap_or("?").to_string(),
+                duration: elapsed.saturating_sub(self.last_artifact),
+            });
+        }
+        self.last_artifact = elapsed;
+
+        let is_cdylib = msg["target"]["kind"]
+            .as_array()
+            .is_some_and(|kinds| kinds.iter().any(|k| k == "cdylib"));
+        if !is_cdylib {
+            return;
+        }
+        let wasm_files = msg["filenames"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter(|f| f.ends_with(".wasm"))
+            .map(PathBuf::from);
+        self.wasm_artifacts.extend(wasm_files);
+    }
+
+    fn process_message(&mut self, message: &Value) {
+        let rendered = message
+            .get("rendered")
+            .or_else(|| message.get("message"))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string();
+        match message.get("level").and_then(Value::as_str) {
+            Some("warning") => self.warnings.push(rendered),
+            Some("error") => self.errors.push(rendered),
+            _ => (),
+        }
+    }
+}
+
+/// Describes where two (different) binaries diverge
+pub fn describe_difference(a: &[u8], b: &[u8]) -> String {
+    let first_diff = a.iter().zip(b).position(|(x, y)| x != y);
+    let differing = a.iter().zip(b).filter(|(x, y)| x != y).count();
+    let mut out = format!(
+        "sizes: {} vs {} bytes, {differing} differing bytes",
+        a.len(),
+        b.len()
+    );
+    match first_diff {
+        Some(offset) => out.push_str(&format!(", first difference at offset {offset:#x}")),
+        None => out.push_str(&format!(
+            ", binaries diverge after offset {:#x}",
+            a.len().min(b.len())
+        )),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cargo_diagnostics() {
+        let mut output = CargoOutput::default();
+        let t = Duration::ZERO;
+        output.process_line(
+            r#"{"reason":"build-script-executed","package_id":"serde"}"#,
+            t,
+        );
+        output.process_line(r#"{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `x`","rendered":"warning: unused variable: `x`\n"}}"#, t);
+        output.process_line(r#"{"reason":"compiler-message","message":{"level":"error","message":"mismatched types"}}"#, t);
+        output.process_line("   Compiling my-contract v0.1.0", t);
+        assert_eq!(output.warnings, ["warning: unused variable: `x`"]);
+        assert_eq!(output.errors, ["mismatched types"]);
+    }
+
+    #[test]
+    fn cargo_artifacts() {
+        // Captured from `cargo build --release --target=wasm32-unknown-unknown --message-format=json`
+        let captured = r#"{"reason":"compiler-artifact","package_id":"registry+https://github.com/rust-lang/crates.io-index#serde@1.0.219","manifest_path":"/home/user/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f/serde-1.0.219/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"serde","src_path":"/home/user/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f/serde-1.0.219/src/lib.rs","edition":"2018","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"3","debuginfo":0,"debug_assertions":false,"overflow_checks":false,"test":false},"features":["default","derive","serde_derive","std"],"filenames":["/tmp/my-contract/target/wasm32-unknown-unknown/release/deps/libserde-2c4a3f5f0c1e9d7b.rlib","/tmp/my-contract/target/wasm32-unknown-unknown/release/deps/libserde-2c4a3f5f0c1e9d7b.rmeta"],"executable":null,"fresh":true}
+{"reason":"compiler-message","package_id":"path+file:///tmp/my-contract#0.1.0","manifest_path":"/tmp/my-contract/Cargo.toml","target":{"kind":["cdylib"],"crate_types":["cdylib"],"name":"my_contract","src_path":"/tmp/my-contract/src/lib.rs","edition":"2021","doc":true,"doctest":false,"test":true},"message":{"rendered":"warning: unused variable: `x`\n --> src/lib.rs:3:9\n","$message_type":"diagnostic","children":[],"code":{"code":"unused_variables","explanation":null},"level":"warning","message":"unused variable: `x`","spans":[]}}
+{"reason":"compiler-artifact","package_id":"path+file:///tmp/my-contract#0.1.0","manifest_path":"/tmp/my-contract/Cargo.toml","target":{"kind":["cdylib"],"crate_types":["cdylib"],"name":"my_contract","src_path":"/tmp/my-contract/src/lib.rs","edition":"2021","doc":true,"doctest":false,"test":true},"profile":{"opt_level":"3","debuginfo":0,"debug_assertions":false,"overflow_checks":false,"test":false},"features":[],"filenames":["/tmp/my-contract/target/wasm32-unknown-unknown/release/my_contract.wasm"],"executable":null,"fresh":false}
+{"reason":"build-finished","success":true}"#;
+        let mut output = CargoOutput::default();
+        for (secs, line) in captured.lines().enumerate() {
+            output.process_line(line, Duration::from_secs(secs as u64));
+        }
+        assert_eq!(
+            output.wasm_artifacts,
+            [PathBuf::from(
+                "/tmp/my-contract/target/wasm32-unknown-unknown/release/my_contract.wasm"
+            )]
+        );
+        assert_eq!(output.warnings.len(), 1);
+        // serde is fresh, so only the contract itself was compiled
+        assert_eq!(
+            output.units,
+            [CompiledUnit {
+                name: "my_contract".to_string(),
+                duration: Duration::from_secs(2),
+            }]
+        );
+    }
+
+    #[test]
+    fn binary_differences() {
+        assert_eq!(
+            describe_difference(b"\0asm1234", b"\0asm1x3y"),
+            "sizes: 8 vs 8 bytes, 2 differing bytes, first difference at offset 0x5"
+        );
+        assert_eq!(
+            describe_difference(b"\0asm", b"\0asm12"),
+            "sizes: 4 vs 6 bytes, 0 differing bytes, binaries diverge after offset 0x4"
+        );
+    }
+}
@@ -1,15 +1,29 @@
+mod config;
+mod convert;
 mod deploy;
+mod info;
 mod init;
 mod link;
+mod manifest;
 mod merge;
 mod pack;
 mod publish;
+mod pull;
+mod registry;
+mod status;
 mod template;
 
 // Re-export functions from sub-modules here
+pub use config::handle_config;
+pub use convert::handle_convert;
 pub use deploy::handle_deploy;
+pub use info::handle_info;
 pub use init::handle_init;
 pub use link::handle_link;
+pub use manifest::handle_manifest;
 pub use merge::handle_merge;
 pub use pack::handle_pack;
+pub use pull::handle_pull;
+pub use registry::handle_registry;
+pub use status::handle_status;
 pub use template::handle_template;
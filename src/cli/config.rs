@@ -0,0 +1,55 @@
+use anyhow::{bail, Result};
+use cliclack::{intro, outro};
+
+use crate::{
+    config::{config_file, validate_config, write_config, Config},
+    error::CliError,
+    log::{error, info, success},
+    ConfigCmd,
+};
+
+pub fn handle_config(cmd: ConfigCmd, assume_yes: bool) -> Result<()> {
+    match cmd {
+        ConfigCmd::Init { force } => init(force, assume_yes)?,
+        ConfigCmd::Validate => validate()?,
+    }
+    Ok(())
+}
+
+/// Reports all problems of the config file and fails, if there are any
+fn validate() -> Result<()> {
+    let Some(file) = config_file() else {
+        info("No config file found - the default config is used")?;
+        return Ok(());
+    };
+    let problems = validate_config(&file)?;
+    if problems.is_empty() {
+        success(format!("Config '{}' is valid", file.display()))?;
+        return Ok(());
+    }
+    for problem in &problems {
+        error(problem)?;
+    }
+    bail!(CliError::usage(format!(
+        "config '{}' has {} problems",
+        file.display(),
+        problems.len()
+    )))
+}
+
+fn init(force: bool, assume_yes: bool) -> Result<()> {
+    intro("⚙ Initialize default config")?;
+
+    if let Some(existing) = config_file() {
+        if !force {
+            bail!(CliError::usage(format!(
+                "config already exists at '{}' - use '--force' to overwrite it",
+                existing.display()
+            )));
+        }
+    }
+
+    let path = write_config(&Config::default(), assume_yes)?;
+    outro(format!("Wrote default config to '{}'", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use cliclack::intro;
+
+use crate::{
+    error::CliError,
+    log::success,
+    package::{read_package, PkgFormat},
+    util::{confirm_write, ensure_file, write_atomic},
+    ConvertArgs,
+};
+
+pub fn handle_convert(args: ConvertArgs, assume_yes: bool) -> Result<()> {
+    ensure_file(&args.input, "package")?;
+
+    intro("⟡ Converting package ...")?;
+
+    let package = read_package(&args.input)?;
+    let name = package.name.clone();
+    let digest = package.source.digest;
+
+    let output = args
+        .output
+        .unwrap_or_else(|| default_output(&args.input, args.to));
+    if output == args.input {
+        bail!(CliError::usage(format!(
+            "refusing to overwrite the input file '{}' - use --output to choose another file",
+            output.display()
+        )));
+    }
+
+    let encoded = args.to.encode(package)?;
+
+    // Decode the result again, so we never write a package that cannot be read back
+    let converted = args.to.decode(&encoded)?;
+    if converted.source.digest != digest {
+        bail!(
+            "digest changed during conversion: {} vs {}",
+            String::from(digest),
+            String::from(converted.source.digest)
+        );
+    }
+
+    confirm_write(&output, assume_yes)?;
+    write_atomic(&output, &encoded)?;
+
+    success(format!(
+        "Converted package '{name}' to {:?}, digest = {}, output = {}",
+        args.to,
+        String::from(digest),
+        output.display()
+    ))?;
+    Ok(())
+}
+
+/// Places the converted package next to the input, with the default file name of the format
+fn default_output(input: &Path, format: PkgFormat) -> PathBuf {
+    input.parent().unwrap_or(input).join(format.file_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converted_output_is_next_to_input() {
+        assert_eq!(
+            default_output(Path::new("some/dir/package.json"), PkgFormat::Postcard),
+            PathBuf::from("some/dir/package.postcard")
+        );
+    }
+}
@@ -1,31 +1,296 @@
-use std::{fs, path::PathBuf};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
 
-use anyhow::{bail, Result};
-use borderless::common::IntroductionDto;
-use cliclack::{intro, outro};
+use anyhow::{bail, Context, Result};
+use borderless::{
+    common::{Id, IntroductionDto},
+    BorderlessId,
+};
+use cliclack::{confirm, intro, outro, spinner};
+use serde_json::Value;
 
-use crate::api::Node;
+use crate::{
+    api::{Environment, Node, WrittenIntroduction},
+    config::get_config,
+    error::CliError,
+    log::{info, warning},
+    package::{check_digest, merge_package, read_package, validate_package},
+    util::{check_schema, ensure_input, ensure_interactive, read_buffered},
+    DeployArgs, OutputFormat,
+};
 
-pub fn handle_deploy(path: PathBuf) -> Result<()> {
-    intro("🚀 Preparing to deploy ...")?;
+/// Interval between two status requests when waiting for a deployment
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub fn handle_deploy(args: DeployArgs, assume_yes: bool) -> Result<()> {
+    if !args.quiet {
+        intro("🚀 Preparing to deploy ...")?;
+    }
 
     let node = Node::select()?;
 
-    // Read introduction
-    if !path.exists() {
-        bail!("{} does not exist", path.display());
+    // Read introduction ( and merge the package into it, without writing anything to disk )
+    let (path, mut introduction) = match (&args.path, &args.from_package, &args.introduction) {
+        (_, Some(package_path), Some(intro_path)) => {
+            if package_path == intro_path {
+                bail!(CliError::usage(
+                    "package and introduction cannot both be read from the same input"
+                ));
+            }
+            ensure_input(intro_path, "introduction")?;
+            ensure_input(package_path, "package definition")?;
+            let mut introduction: Value = read_buffered(intro_path)?;
+            merge_package(&mut introduction, read_package(package_path)?)?;
+            (intro_path, introduction)
+        }
+        (Some(path), _, _) => {
+            ensure_input(path, "introduction")?;
+            (path, read_buffered(path)?)
+        }
+        _ => bail!(CliError::usage(
+            "either an introduction or --from-package and --introduction are required"
+        )),
+    };
+    if introduction.get("package").is_none_or(is_empty) {
+        bail!(CliError::usage(format!(
+            "introduction '{}' does not contain a package - use 'borderless merge' to add one",
+            path.display()
+        )));
+    }
+    if let Some(ids) = &args.participants {
+        let participants = parse_participants(ids)?;
+        if !args.quiet {
+            info(format!(
+                "Replacing the participants of '{}' with {} participants from --participants",
+                path.display(),
+                participants.len()
+            ))?;
+        }
+        replace_participants(&mut introduction, participants)?;
+    }
+    // The schema applies to what is actually sent
+    if let Some(schema_path) = &args.schema {
+        check_schema(schema_path, &introduction, "introduction")?;
+    }
+    let introduction: IntroductionDto = serde_json::from_value(introduction)
+        .with_context(|| format!("failed to parse '{}'", path.display()))?;
+    // Never send a package, whose code does not match its digest
+    validate_package(&introduction.package)
+        .with_context(|| format!("refusing to deploy '{}'", path.display()))?;
+    if let Some(expected) = &args.expected_digest {
+        check_digest(&introduction.package, expected)
+            .with_context(|| format!("refusing to deploy '{}'", path.display()))?;
+    }
+    let predefined_id = introduction.id;
+
+    // Show what we are about to send
+    let link = node.link();
+    if !args.quiet {
+        info(format!(
+            "Target:       {} ({})\nPackage:      {} {}\nParticipants: {}",
+            link.name,
+            link.api,
+            introduction.package.name,
+            introduction.package.source.version,
+            introduction.participants.len()
+        ))?;
+    }
+    let is_dev_node =
+        link.environment == Some(Environment::Dev) || get_config().dev_nodes.contains(&link.name);
+    if link.environment == Some(Environment::Prod) {
+        warning(format!("'{}' is a production node", link.name))?;
+    }
+    if args.preflight {
+        preflight(&node)?;
+    }
+    if !assume_yes && (args.preflight || !is_dev_node) {
+        ensure_interactive("pass --yes to confirm the deployment")?;
+        let action = if args.upgrade {
+            "Upgrade on"
+        } else {
+            "Deploy to"
+        };
+        if !confirm(format!("{action} '{}'?", link.name)).interact()? {
+            bail!("Process aborted by user.");
+        }
     }
-    if !path.is_file() {
-        bail!("{} is not a file", path.display());
+
+    let written = match node.write_introduction(introduction, args.upgrade)? {
+        Some(written) => written,
+        None => bail!(CliError::network("Failed to write introduction")),
+    };
+
+    // Prefer the id that was assigned by the node
+    let id = written.id.or(predefined_id);
+
+    if args.wait {
+        match &id {
+            Some(id) => wait_until_active(&node, id, Duration::from_secs(args.wait_timeout))?,
+            None => bail!("cannot wait for deployment - node did not return an id"),
+        }
     }
-    let content = fs::read(path)?;
-    let introduction: IntroductionDto = serde_json::from_slice(&content)?;
 
-    if node.write_introduction(introduction)? {
-        outro("Wrote introduction")?;
-    } else {
-        outro("Failed to write introduction")?;
+    if args.format == OutputFormat::Json {
+        let result = WrittenIntroduction { id, ..written };
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
     }
+    match (id, args.quiet) {
+        (Some(id), true) => println!("{id}"),
+        (Some(id), false) if args.upgrade => {
+            outro(format!("Upgraded {id} on '{}'", node.link().name))?
+        }
+        (Some(id), false) => outro(format!("Deployed {id} to '{}'", node.link().name))?,
+        (None, true) => (),
+        (None, false) => outro("Wrote introduction")?,
+    }
+
+    Ok(())
+}
 
+/// Shows the node-info and the number of peers, so the user can check the network and organization of the node
+fn preflight(node: &Node) -> Result<()> {
+    let node_info = node
+        .node_info()
+        .context("preflight failed - cannot query the node-info")?;
+    let peers = node
+        .network_peers(None)
+        .context("preflight failed - cannot query the peers")?;
+    info(format!(
+        "Node-Info:\n{}\nPeers: {}",
+        serde_json::to_string_pretty(&node_info)?,
+        peers.len()
+    ))?;
     Ok(())
 }
+
+/// Parses the ids given via `--participants`
+fn parse_participants(ids: &[String]) -> Result<Vec<BorderlessId>> {
+    let mut participants = Vec::with_capacity(ids.len());
+    for id in ids.iter().map(|id| id.trim()) {
+        let pid: BorderlessId = id.parse().map_err(|e| {
+            CliError::usage(format!(
+                "invalid participant id '{id}' in --participants: {e}"
+            ))
+        })?;
+        if participants.contains(&pid) {
+            bail!(CliError::usage(format!(
+                "participant '{id}' is given twice in --participants"
+            )));
+        }
+        participants.push(pid);
+    }
+    Ok(participants)
+}
+
+/// Replaces ( or sets ) the participants of an introduction
+fn replace_participants(introduction: &mut Value, participants: Vec<BorderlessId>) -> Result<()> {
+    let Value::Object(map) = introduction else {
+        bail!("introduction must be a json-object");
+    };
+    map.insert(
+        "participants".to_string(),
+        serde_json::to_value(participants)?,
+    );
+    Ok(())
+}
+
+/// Returns true for `null` and `{}`
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
+/// Polls the node until the contract or agent is active or the timeout elapsed
+fn wait_until_active(node: &Node, id: &Id, timeout: Duration) -> Result<()> {
+    let sp = spinner();
+    sp.start(format!("Waiting for {id} to become active..."));
+
+    let start = Instant::now();
+    let mut last_status = None;
+    loop {
+        let status = match node.introduction_status(id) {
+            Ok(status) => status,
+            Err(e) => {
+                sp.error("Failed to query status");
+                return Err(e);
+            }
+        };
+        match status.as_deref() {
+            Some(s) if s.eq_ignore_ascii_case("active") => {
+                sp.stop(format!("{id} is active"));
+                return Ok(());
+            }
+            Some(s) => sp.set_message(format!("Status of {id}: {s}")),
+            None => sp.set_message(format!("Waiting for node to process {id}...")),
+        }
+        last_status = status.or(last_status);
+
+        if start.elapsed() >= timeout {
+            let last = last_status.unwrap_or_else(|| "unknown".to_string());
+            sp.error(format!("Timeout - last status: {last}"));
+            bail!(CliError::network(format!(
+                "{id} did not become active within {}s ( last status: {last} )",
+                timeout.as_secs()
+            )));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn participants_before_introduction() {
+        use clap::Parser;
+        let id = "0bc23cb3-f447-8107-8f93-9bfb8e1d157d";
+        let cli = crate::Cli::parse_from([
+            "borderless",
+            "deploy",
+            "--participants",
+            &format!("{id},{id}"),
+            "intro.json",
+        ]);
+        let crate::Commands::Deploy(args) = cli.command else {
+            unreachable!()
+        };
+        assert_eq!(
+            args.path.as_deref(),
+            Some(std::path::Path::new("intro.json"))
+        );
+        assert_eq!(args.participants.unwrap(), [id, id]);
+    }
+
+    #[test]
+    fn participants_override() -> Result<()> {
+        let ids = [
+            "0bc23cb3-f447-8107-8f93-9bfb8e1d157d".to_string(),
+            " 0bc23cb3-f447-8107-8f93-9bfb8e1d1500".to_string(),
+        ];
+        let participants = parse_participants(&ids)?;
+        assert_eq!(participants.len(), 2);
+
+        let mut introduction = serde_json::json!({ "participants": ["old"], "package": null });
+        replace_participants(&mut introduction, participants.clone())?;
+        let replaced: Vec<BorderlessId> =
+            serde_json::from_value(introduction["participants"].clone())?;
+        assert_eq!(replaced, participants);
+        assert!(replace_participants(&mut serde_json::json!([]), participants).is_err());
+
+        let err = parse_participants(&["alice".to_string()]).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid participant id 'alice'"),
+            "{err}"
+        );
+        let err = parse_participants(&[ids[0].clone(), ids[0].clone()]).unwrap_err();
+        assert!(err.to_string().contains("given twice"), "{err}");
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use std::{env, path::PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    api::{api_version, read_links, LINK_DB_FILE},
+    cli::pack::{wasm_target_installed, Toolchain, WASM_TARGET},
+    config::{config_file, get_config, profile_file},
+    InfoArgs, OutputFormat,
+};
+
+/// Everything about the environment of the cli, that is relevant for a bug report
+#[derive(Debug, Serialize)]
+struct EnvironmentInfo {
+    cli_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    config_file: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    profile: String,
+    api_version: &'static str,
+    /// Number of links ( the links themselves may contain secrets )
+    links: usize,
+    toolchain: Option<Toolchain>,
+    /// `None`, if rustup is not available
+    wasm_target_installed: Option<bool>,
+}
+
+pub fn handle_info(args: InfoArgs) -> Result<()> {
+    let config = get_config();
+    let current_dir = env::current_dir()?;
+    let info = EnvironmentInfo {
+        cli_version: env!("CARGO_PKG_VERSION"),
+        os: env::consts::OS,
+        arch: env::consts::ARCH,
+        config_file: config_file(),
+        data_dir: config.data_dir().ok(),
+        profile: config.profile().to_string(),
+        api_version: api_version(),
+        links: count_links()?,
+        // A missing toolchain is part of the report, not an error
+        toolchain: Toolchain::detect(&current_dir).ok(),
+        wasm_target_installed: wasm_target_installed(&current_dir, WASM_TARGET),
+    };
+
+    let format = if args.json {
+        OutputFormat::Json
+    } else {
+        args.format
+    };
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+        OutputFormat::Text => {
+            for (key, value) in info.rows() {
+                println!("{key:<16}{value}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Counts the links of the selected profile ( without creating the link-db )
+fn count_links() -> Result<usize> {
+    let db = profile_file(LINK_DB_FILE)?;
+    if !db.is_file() {
+        return Ok(0);
+    }
+    Ok(read_links(&db)?.len())
+}
+
+impl EnvironmentInfo {
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        let path = |p: &Option<PathBuf>| {
+            p.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let (rustc, cargo) = match &self.toolchain {
+            Some(toolchain) => (toolchain.to_string(), toolchain.cargo.clone()),
+            None => ("not found".to_string(), "not found".to_string()),
+        };
+        let wasm_target = match self.wasm_target_installed {
+            Some(true) => "installed",
+            Some(false) => "missing",
+            None => "unknown ( rustup not found )",
+        };
+        [
+            ("Version", self.cli_version.to_string()),
+            ("OS", format!("{} ( {} )", self.os, self.arch)),
+            ("Config", path(&self.config_file)),
+            ("Data-directory", path(&self.data_dir)),
+            ("Profile", self.profile.clone()),
+            ("API-version", self.api_version.to_string()),
+            ("Links", self.links.to_string()),
+            ("rustc", rustc),
+            ("cargo", cargo),
+            ("wasm-target", wasm_target.to_string()),
+        ]
+        .into()
+    }
+}
@@ -1,9 +1,13 @@
-use crate::config::get_config;
-use crate::template::{generate_lib_rs, generate_manifest};
-use anyhow::{bail, Result};
-use borderless_pkg::PkgType;
-use cliclack::{confirm, select};
-use cliclack::{input, intro, log::info, log::success};
+use crate::config::{get_config, write_config};
+use crate::error::CliError;
+use crate::log::{info, success, warning};
+use crate::template::{generate_lib_rs, generate_manifest, init_templates, InitTemplate};
+use crate::util::{confirm_creation, ensure_interactive, format_table};
+use crate::{Capability, InitArgs};
+use anyhow::{bail, Context, Result};
+use borderless_pkg::{Author, Capabilities, PkgType};
+use cliclack::{confirm, multiselect, select};
+use cliclack::{input, intro};
 use std::path::{Path, PathBuf};
 use std::{env, fs};
 
@@ -29,28 +33,36 @@ fn validate_name(input: &String) -> Result<(), &'static str> {
 /// - a name for the package that will be created ( `borderless init my-contract` )
 /// - a directory, where the new package will be created ( `borderless init ./foo` )
 /// - a reference to a github repo, that should serve as a template ( `borderless init @owner/repo:1.2.1` )
-pub fn handle_init(name_or_path: Option<String>) -> Result<()> {
+///
+/// With `--force`, an existing directory is scaffolded in place ( `borderless init ./my-crate --force` ).
+pub fn handle_init(args: InitArgs, assume_yes: bool) -> Result<()> {
+    if args.list_templates {
+        return list_templates();
+    }
     intro("Initialize a new package 📦")?;
-    let pkg_type = select("Please select the package type:")
-        .item(
-            PkgType::Contract,
-            "Contract 🔗  ",
-            "initializes a SmartContract",
-        )
-        .item(
-            PkgType::Agent,
-            "Agent    🤖✨",
-            "initializes a Software-Agent",
-        )
-        .initial_value(PkgType::Contract)
-        .interact()?;
+    let template = select_template(args.template.as_deref(), args.pkg_type.map(Into::into))?;
+    let version = template.version()?;
+    if let Some(expected) = &args.template_version {
+        if *expected != version {
+            bail!(CliError::usage(format!(
+                "template '{}' has version {version}, but {expected} was requested",
+                template.name
+            )));
+        }
+    }
+    info(format!(
+        "Using template '{}' ( version {version} )",
+        template.name
+    ))?;
+    let pkg_type = template.pkg_type.clone();
+    let capabilities = select_capabilities(&pkg_type, args.capabilities)?;
 
     let (type_str, placeholder) = match pkg_type {
         PkgType::Contract => ("Contract", "my-contract"),
         PkgType::Agent => ("Agent", "my-agent"),
     };
 
-    let name_or_path = name_or_path.unwrap_or(".".to_string());
+    let name_or_path = args.project_name.unwrap_or(".".to_string());
     let try_path = PathBuf::from(name_or_path.clone());
 
     // If the given input is an existing path, we query for the name of the contract that should be created
@@ -58,11 +70,28 @@ pub fn handle_init(name_or_path: Option<String>) -> Result<()> {
         if !try_path.is_dir() {
             bail!("{} is not a directory", try_path.display());
         }
-        let pkg_name = input(format!("{type_str} name"))
-            .placeholder(placeholder)
-            .validate(validate_name)
-            .interact()?;
-        (pkg_name, try_path)
+        // With force, the existing directory itself is scaffolded
+        if args.force {
+            let dir = fs::canonicalize(&try_path)?;
+            let name = dir
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .with_context(|| format!("cannot scaffold into '{}'", dir.display()))?;
+            validate_name(&name).map_err(anyhow::Error::msg)?;
+            let parent = dir.parent().map(Path::to_path_buf).unwrap_or_default();
+            (name, parent)
+        } else {
+            ensure_interactive(format!(
+                "pass the {} name as argument or use --force to scaffold '{}' itself",
+                type_str.to_lowercase(),
+                try_path.display()
+            ))?;
+            let pkg_name = input(format!("{type_str} name"))
+                .placeholder(placeholder)
+                .validate(validate_name)
+                .interact()?;
+            (pkg_name, try_path)
+        }
     } else {
         // If it is not an existing path, it could be a path we should create.
         //
@@ -86,32 +115,148 @@ pub fn handle_init(name_or_path: Option<String>) -> Result<()> {
 
     // check the project path
     if project_path.exists() {
-        bail!("Directory '{}' already exists", project_path.display());
-    }
+        if !args.force {
+            bail!("Directory '{}' already exists", project_path.display());
+        }
+        if !project_path.is_dir() {
+            bail!("{} is not a directory", project_path.display());
+        }
+        info(format!(
+            "Using existing project directory: {}",
+            project_path.display()
+        ))?;
+    } else {
+        confirm_creation(
+            format!("Create project directory: {}", project_path.display()),
+            assume_yes,
+        )?;
 
-    if get_config().confirm_creation
-        && !confirm(format!(
-            "Create project directory: {}",
+        // create project path
+        fs::create_dir_all(&project_path)?;
+
+        info(format!(
+            "Created project directory: {}",
             project_path.display()
-        ))
-        .interact()?
-    {
-        bail!("Process aborted by user.");
+        ))?;
     }
 
-    // create project path
-    fs::create_dir_all(&project_path)?;
-
-    info(format!(
-        "Created project directory: {}",
-        project_path.display()
-    ))?;
+    create_project_structure(
+        &project_path,
+        pkg_name,
+        &template,
+        &capabilities,
+        args.author,
+        args.with_tests,
+        args.force,
+    )?;
 
-    create_project_structure(&project_path, pkg_name, pkg_type)?;
+    Ok(())
+}
 
+/// Prints the embedded templates and their versions
+fn list_templates() -> Result<()> {
+    let mut rows = Vec::new();
+    for template in init_templates() {
+        let pkg_type = match template.pkg_type {
+            PkgType::Contract => "contract",
+            PkgType::Agent => "agent",
+        };
+        rows.push([
+            template.name.clone(),
+            pkg_type.to_string(),
+            template.version()?,
+            template.hint,
+        ]);
+    }
+    print!(
+        "{}",
+        format_table(["NAME", "TYPE", "VERSION", "DESCRIPTION"], &rows)
+    );
     Ok(())
 }
 
+/// Selects the template by name, by package type or interactively
+fn select_template(name: Option<&str>, pkg_type: Option<PkgType>) -> Result<InitTemplate> {
+    let templates = init_templates();
+    if let Some(name) = name {
+        let available: Vec<_> = templates.iter().map(|t| t.name.clone()).collect();
+        return templates
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| {
+                CliError::usage(format!(
+                    "unknown template '{name}' - available templates: {}",
+                    available.join(", ")
+                ))
+                .into()
+            });
+    }
+    if let Some(pkg_type) = pkg_type {
+        return templates
+            .into_iter()
+            .find(|t| t.pkg_type == pkg_type)
+            .context("missing template for package type");
+    }
+
+    // Preselect the first template of the configured package type
+    let default_type = get_config()
+        .default_package_type
+        .clone()
+        .unwrap_or(PkgType::Contract);
+    let initial = templates
+        .iter()
+        .position(|t| t.pkg_type == default_type)
+        .unwrap_or_default();
+    ensure_interactive("pass --template <name> or --type <contract|agent>")?;
+    let mut prompt = select("Please select a template:");
+    for (idx, template) in templates.iter().enumerate() {
+        prompt = prompt.item(idx, &template.label, &template.hint);
+    }
+    let idx = prompt.initial_value(initial).interact()?;
+    Ok(templates[idx].clone())
+}
+
+/// Selects the capabilities of an agent, either from the commandline or interactively
+///
+/// Contracts have no capabilities, so the prompt is skipped for them.
+fn select_capabilities(
+    pkg_type: &PkgType,
+    selected: Option<Vec<Capability>>,
+) -> Result<Capabilities> {
+    let selected = match (pkg_type, selected) {
+        (PkgType::Contract, Some(_)) => bail!(CliError::usage(
+            "--capabilities can only be used for agents, contracts have no capabilities"
+        )),
+        (PkgType::Contract, None) => Vec::new(),
+        (PkgType::Agent, Some(selected)) => {
+            if selected.contains(&Capability::None) && selected.len() > 1 {
+                bail!(CliError::usage(
+                    "--capabilities none cannot be combined with other capabilities"
+                ));
+            }
+            selected
+        }
+        (PkgType::Agent, None) => {
+            ensure_interactive("pass --capabilities ( e.g. --capabilities network,websocket )")?;
+            multiselect("Which capabilities does the agent need?")
+                .item(Capability::Network, "network", "make http-calls")
+                .item(
+                    Capability::Websocket,
+                    "websocket",
+                    "establish websocket connections",
+                )
+                .initial_values(vec![Capability::Network, Capability::Websocket])
+                .required(false)
+                .interact()?
+        }
+    };
+    Ok(Capabilities {
+        network: selected.contains(&Capability::Network),
+        websocket: selected.contains(&Capability::Websocket),
+        url_whitelist: Vec::new(),
+    })
+}
+
 fn check_existence(path: &Path) -> Result<()> {
     if path.exists() {
         bail!(
@@ -125,7 +270,11 @@ fn check_existence(path: &Path) -> Result<()> {
 fn create_project_structure(
     project_path: &Path,
     pkg_name: String,
-    pkg_type: PkgType,
+    template: &InitTemplate,
+    capabilities: &Capabilities,
+    author: Option<Author>,
+    with_tests: bool,
+    force: bool,
 ) -> Result<()> {
     // src dir and basic files
     let src = project_path.join("src");
@@ -133,33 +282,47 @@ fn create_project_structure(
     let cargo_file = project_path.join("Cargo.toml");
     let manifest_file = project_path.join("Manifest.toml");
 
-    // Sanity check
-    check_existence(&src)?;
-    check_existence(&lib_file)?;
-    check_existence(&cargo_file)?;
-    check_existence(&manifest_file)?;
+    // Sanity check ( with force, existing files are skipped instead )
+    if !force {
+        check_existence(&src)?;
+        check_existence(&lib_file)?;
+        check_existence(&cargo_file)?;
+        check_existence(&manifest_file)?;
+    }
 
     // Create src directory
     fs::create_dir_all(&src)?;
 
-    // Get author
-    let author = if let Some(author) = &get_config().author {
-        author.to_string()
-    } else {
-        query_author()?
+    // Get author ( the commandline takes precedence over the config )
+    let author = match author.as_ref().or(get_config().author.as_ref()) {
+        Some(author) => author.to_string(),
+        None => query_author()?,
     };
 
-    // Create Cargo.toml
-    let cargo_toml_content = build_cargo_toml(&pkg_name, &author)?;
-    fs::write(&cargo_file, cargo_toml_content)?;
-
-    // Create Manifest.toml
-    let manifest = generate_manifest(&pkg_name, &pkg_type, vec![author])?;
-    fs::write(&manifest_file, manifest)?;
-
-    // Create src/lib.rs
-    let lib_rs_content = generate_lib_rs(&pkg_name, &pkg_type)?;
-    fs::write(&lib_file, lib_rs_content)?;
+    // Cargo.toml, Manifest.toml and src/lib.rs
+    let pkg_type = &template.pkg_type;
+    let manifest = format!(
+        "{}{}",
+        template.manifest_header()?,
+        generate_manifest(&pkg_name, pkg_type, vec![author.clone()], capabilities)?
+    );
+    let files = [
+        (cargo_file, build_cargo_toml(&pkg_name, &author)?),
+        (manifest_file, manifest),
+        (lib_file, generate_lib_rs(&pkg_name, pkg_type, with_tests)?),
+    ];
+    for (file, content) in files {
+        let rel_path = file.strip_prefix(project_path).unwrap_or(&file);
+        if check_existence(&file).is_err() {
+            warning(format!("Skipped existing file {}", rel_path.display()))?;
+            continue;
+        }
+        fs::write(&file, content)
+            .with_context(|| format!("failed to write '{}'", file.display()))?;
+        if force {
+            info(format!("Created {}", rel_path.display()))?;
+        }
+    }
 
     success("Generated project files. Happy coding 💻!")?;
     Ok(())
@@ -223,7 +386,10 @@ fn build_cargo_toml(name: &str, author: &str) -> Result<String> {
 }
 
 /// Asks the user for the author
+///
+/// The email is optional; without one, the author consists only of the name.
 pub fn query_author() -> Result<String> {
+    ensure_interactive("pass --author or set the `author` field in your config")?;
     info("Please tell us who you are. If you don't want to input these values everytime, you can set the `author` field in your config.")?;
     let author: String = input("Name:")
         .placeholder("John Doe")
@@ -244,12 +410,13 @@ pub fn query_author() -> Result<String> {
         .interact()?;
 
     // Same as with author
-    let email: String = input("Email:")
+    let email: String = input("Email ( optional ):")
         .placeholder("john.doe@example.com")
+        .required(false)
         .validate(|input: &String| {
             let email = input.trim();
             if email.is_empty() {
-                Err("Email cannot be empty")
+                Ok(())
             } else if !email.contains('@') {
                 Err("Email must contain @")
             } else if !email.contains('.') {
@@ -263,5 +430,47 @@ pub fn query_author() -> Result<String> {
             }
         })
         .interact()?;
-    Ok(format!("{} <{}>", author, email))
+    let author = match email.trim() {
+        "" => author.trim().to_string(),
+        email => format!("{} <{}>", author.trim(), email),
+    };
+
+    if confirm("Save these to your config?").interact()? {
+        let mut config = get_config().clone();
+        config.author = Some(author.parse().map_err(anyhow::Error::msg)?);
+        let path = write_config(&config, true)?;
+        info(format!("Saved author to '{}'", path.display()))?;
+    }
+    Ok(author)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn init_args(args: &[&str]) -> InitArgs {
+        let cli = crate::Cli::parse_from(["borderless", "init"].iter().chain(args));
+        match cli.command {
+            crate::Commands::Init(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn capabilities_before_project_name() -> Result<()> {
+        let args = init_args(&["--capabilities", "network,websocket", "my-agent"]);
+        assert_eq!(args.project_name.as_deref(), Some("my-agent"));
+        let capabilities = select_capabilities(&PkgType::Agent, args.capabilities)?;
+        assert!(capabilities.network && capabilities.websocket);
+
+        let args = init_args(&["--capabilities", "none", "my-agent"]);
+        assert_eq!(args.project_name.as_deref(), Some("my-agent"));
+        let capabilities = select_capabilities(&PkgType::Agent, args.capabilities)?;
+        assert!(!capabilities.network && !capabilities.websocket);
+
+        let args = init_args(&["--capabilities", "none,network"]);
+        assert!(select_capabilities(&PkgType::Agent, args.capabilities).is_err());
+        Ok(())
+    }
 }
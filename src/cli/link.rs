@@ -1,23 +1,59 @@
-use anyhow::Result;
-use cliclack::{confirm, input, intro, log::info, outro, select};
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use cliclack::{confirm, input, intro, outro, password, select};
+use serde::Serialize;
 use url::Url;
 
-use crate::api::{Link, LinkDb};
+use crate::{
+    api::{
+        read_links, ConflictStrategy, Environment, Imported, Link, LinkDb, LinkKind, Node,
+        DEFAULT_API_VERSION,
+    },
+    error::CliError,
+    log::{info, warning},
+    util::{ensure_file, ensure_interactive, format_table},
+    ImportLinksArgs, LinkArgs, LinkCmd, OutputFormat, RefreshLinkArgs, ShowLinkArgs, TestAllArgs,
+};
+
+/// Placeholder for secrets, that are not shown
+const MASKED: &str = "********";
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Item {
-    Existing(Link),
+    Existing(Box<Link>),
     Create,
 }
 
-pub fn handle_link() -> Result<()> {
+pub fn handle_link(args: LinkArgs) -> Result<()> {
+    match args.command {
+        Some(LinkCmd::TestAll(test_args)) => return test_all(test_args),
+        Some(LinkCmd::Show(show_args)) => return show(show_args),
+        Some(LinkCmd::Import(import_args)) => return import(import_args),
+        Some(LinkCmd::Refresh(refresh_args)) => return refresh(refresh_args),
+        None => (),
+    }
+
+    // Read the key first, so we fail before any prompt is shown
+    let preset_key = read_api_key(&args)?;
+    ensure_interactive("use 'borderless link import <file>' to add links non-interactively")?;
+
     intro("🔗 Creating or modifying links to external nodes")?;
 
     // Get existing links
     let db = LinkDb::open()?;
 
     // Select link to modify or create new link
-    let mut selectable: Vec<_> = db.get_links().into_iter().map(Item::Existing).collect();
+    let mut selectable: Vec<_> = db
+        .get_links()
+        .into_iter()
+        .map(|link| Item::Existing(Box::new(link)))
+        .collect();
     selectable.push(Item::Create);
 
     let mut prompt = select("Select existing link or create new one");
@@ -25,7 +61,14 @@ pub fn handle_link() -> Result<()> {
         let (label, hint) = match &item {
             Item::Existing(link) => (
                 link.name.clone(),
-                format!("Contract-Node - {} - <SECRET_API_KEY>", link.api),
+                format!(
+                    "{} - {} - {} - <SECRET_API_KEY>",
+                    link.kind,
+                    link.api,
+                    link.environment
+                        .map(|env| env.to_string())
+                        .unwrap_or_else(|| "unlabeled".to_string())
+                ),
             ),
             Item::Create => (
                 "Create new".to_string(),
@@ -38,16 +81,360 @@ pub fn handle_link() -> Result<()> {
 
     match selection {
         Item::Existing(link) => {
-            modify_existing(db, link)?;
+            modify_existing(db, *link, preset_key)?;
         }
         Item::Create => {
-            create_new(db)?;
+            create_new(db, preset_key)?;
+        }
+    };
+    Ok(())
+}
+
+/// Result of the health check of a single link
+#[derive(Debug, Serialize)]
+struct LinkHealth {
+    name: String,
+    api: Url,
+    reachable: bool,
+    latency_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Requests the node info of every link and prints which nodes are reachable
+fn test_all(args: TestAllArgs) -> Result<()> {
+    let links = LinkDb::open()?.get_links();
+    if args.format == OutputFormat::Text {
+        intro(format!("🩺 Checking {} linked nodes", links.len()))?;
+    }
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut results = Vec::with_capacity(links.len());
+    for link in links {
+        let (name, api) = (link.name.clone(), link.api.clone());
+        let start = Instant::now();
+        let res = Node::with_timeout(link, timeout).and_then(|node| node.node_info());
+        let latency = start.elapsed().as_millis();
+        results.push(match res {
+            Ok(_) => LinkHealth {
+                name,
+                api,
+                reachable: true,
+                latency_ms: Some(latency),
+                error: None,
+            },
+            Err(e) => LinkHealth {
+                name,
+                api,
+                reachable: false,
+                latency_ms: None,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+    let unreachable = results.iter().filter(|r| !r.reachable).count();
+
+    match args.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Text => {
+            let rows: Vec<[String; 4]> = results
+                .iter()
+                .map(|r| {
+                    [
+                        r.name.clone(),
+                        r.api.to_string(),
+                        if r.reachable {
+                            "reachable"
+                        } else {
+                            "unreachable"
+                        }
+                        .to_string(),
+                        r.latency_ms
+                            .map(|ms| format!("{ms} ms"))
+                            .or_else(|| r.error.clone())
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            print!(
+                "{}",
+                format_table(["NAME", "API", "STATUS", "LATENCY"], &rows)
+            );
+        }
+    }
+
+    if args.require_all && unreachable > 0 {
+        bail!(CliError::network(format!(
+            "{unreachable} of {} linked nodes are unreachable",
+            results.len()
+        )));
+    }
+    if args.format == OutputFormat::Text {
+        outro(format!(
+            "{} of {} linked nodes are reachable",
+            results.len() - unreachable,
+            results.len()
+        ))?;
+    }
+    Ok(())
+}
+
+/// All details of a link
+#[derive(Debug, Serialize)]
+struct LinkDetails {
+    #[serde(flatten)]
+    link: Link,
+    /// Version, that is actually used ( the one of the link is optional )
+    api_version: String,
+}
+
+impl LinkDetails {
+    fn new(mut link: Link, show_secret: bool) -> Self {
+        if !show_secret {
+            link.api_key = link.api_key.map(|_| MASKED.to_string());
         }
+        let api_version = link.effective_api_version().to_string();
+        link.api_version = None;
+        LinkDetails { link, api_version }
+    }
+
+    fn rows(&self) -> Vec<[String; 2]> {
+        let path = |p: &Option<PathBuf>| {
+            p.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let link = &self.link;
+        [
+            ("Name", link.name.clone()),
+            ("Kind", link.kind.to_string()),
+            ("API", link.api.to_string()),
+            ("API-version", self.api_version.to_string()),
+            (
+                "Environment",
+                link.environment
+                    .map(|env| env.to_string())
+                    .unwrap_or_else(|| "unlabeled".to_string()),
+            ),
+            (
+                "API-key",
+                link.api_key
+                    .clone()
+                    .unwrap_or_else(|| "not set".to_string()),
+            ),
+            ("CA-cert", path(&link.ca_cert)),
+            ("Client-cert", path(&link.client_cert)),
+            ("Client-key", path(&link.client_key)),
+        ]
+        .map(|(field, value)| [field.to_string(), value])
+        .into()
+    }
+}
+
+/// Returns the link with the given name, or fails with a list of all existing links
+fn existing_link(db: &LinkDb, name: &str) -> Result<Link> {
+    let Some(link) = db.get_link(name) else {
+        let names: Vec<_> = db.get_links().into_iter().map(|l| l.name).collect();
+        bail!(CliError::usage(format!(
+            "found no link with name '{name}' - existing links: {}",
+            names.join(", ")
+        )));
     };
+    Ok(link.clone())
+}
+
+/// Prints a single link
+fn show(args: ShowLinkArgs) -> Result<()> {
+    let db = LinkDb::open()?;
+    let link = existing_link(&db, &args.name)?;
+    let details = LinkDetails::new(link, args.show_secret);
+    match args.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&details)?),
+        OutputFormat::Text => print!("{}", format_table(["FIELD", "VALUE"], &details.rows())),
+    }
+    Ok(())
+}
+
+/// Asks the node of a link for the version of its API again and stores it
+fn refresh(args: RefreshLinkArgs) -> Result<()> {
+    let mut db = LinkDb::open()?;
+    let link = existing_link(&db, &args.name)?;
+    let previous = link.api_version.clone();
+    let version = Node::new(link.clone())?.probe_api_version()?;
+    match (&version, &previous) {
+        (Some(v), Some(p)) if v == p => {
+            info(format!("'{}' still uses api-version {v}", args.name))?
+        }
+        (Some(v), _) => info(format!("'{}' uses api-version {v}", args.name))?,
+        (None, _) => warning(format!(
+            "'{}' does not report its api-version - using {DEFAULT_API_VERSION}",
+            args.name
+        ))?,
+    }
+    if version != previous {
+        db.modify_link(
+            &args.name,
+            Link {
+                api_version: version,
+                ..link
+            },
+        )?;
+        db.commit()?;
+    }
+    Ok(())
+}
+
+/// Negotiates the version of the node API for a new or changed link
+///
+/// An unreachable node is not an error here, since links may be created before the node is running.
+fn negotiate_api_version(link: &Link) -> Result<Option<String>> {
+    let res = Node::new(link.clone()).and_then(|node| node.probe_api_version());
+    match res {
+        Ok(Some(version)) => {
+            info(format!("Node uses api-version {version}"))?;
+            Ok(Some(version))
+        }
+        Ok(None) => {
+            info(format!(
+                "Node does not report its api-version - using {DEFAULT_API_VERSION}"
+            ))?;
+            Ok(None)
+        }
+        Err(e) => {
+            warning(format!(
+                "Failed to query the api-version ( use 'borderless link refresh {}' later ): {e:#}",
+                link.name
+            ))?;
+            Ok(None)
+        }
+    }
+}
+
+/// Imports links from a file
+///
+/// Links that already exist with identical content are ignored.
+/// For every other name collision the strategy from the commandline is applied,
+/// or the user is asked after seeing the differences.
+fn import(args: ImportLinksArgs) -> Result<()> {
+    ensure_file(&args.file, "link file")?;
+    let links = read_links(&args.file)?;
+    intro(format!(
+        "🔗 Importing {} links from '{}'",
+        links.len(),
+        args.file.display()
+    ))?;
+
+    let mut db = LinkDb::open()?;
+    let mut imported = 0;
+    let mut unchanged = 0;
+    for link in links {
+        let strategy = match (db.get_link(&link.name), args.on_conflict) {
+            (Some(existing), None) if *existing != link => {
+                ensure_interactive(format!(
+                    "'{}' already exists; pass --on-conflict to resolve conflicts",
+                    link.name
+                ))?;
+                let rows: Vec<[String; 3]> = existing
+                    .differences(&link)?
+                    .into_iter()
+                    .map(|(field, ours, theirs)| [field, ours, theirs])
+                    .collect();
+                info(format!(
+                    "Link '{}' already exists:\n{}",
+                    link.name,
+                    format_table(["FIELD", "EXISTING", "IMPORTED"], &rows).trim_end()
+                ))?;
+                select(format!("How should '{}' be imported?", link.name))
+                    .item(ConflictStrategy::Skip, "skip", "keep the existing link")
+                    .item(
+                        ConflictStrategy::Overwrite,
+                        "overwrite",
+                        "replace the existing link",
+                    )
+                    .item(
+                        ConflictStrategy::Rename,
+                        "rename",
+                        "import with a numeric suffix",
+                    )
+                    .interact()?
+            }
+            (_, strategy) => strategy.unwrap_or(ConflictStrategy::Skip),
+        };
+        let name = link.name.clone();
+        match db.import_link(link, strategy)? {
+            Imported::Added => info(format!("Added '{name}'"))?,
+            Imported::Unchanged => {
+                info(format!("'{name}' is already linked"))?;
+                unchanged += 1;
+                continue;
+            }
+            Imported::Skipped => {
+                warning(format!("Skipped '{name}'"))?;
+                continue;
+            }
+            Imported::Overwritten => info(format!("Overwrote '{name}'"))?,
+            Imported::Renamed(new_name) => info(format!("Imported '{name}' as '{new_name}'"))?,
+        }
+        imported += 1;
+    }
+    db.commit()?;
+    if unchanged > 0 {
+        outro(format!(
+            "Imported {imported} links, {unchanged} already linked"
+        ))?;
+    } else {
+        outro(format!("Imported {imported} links"))?;
+    }
     Ok(())
 }
 
-fn create_new(mut db: LinkDb) -> Result<()> {
+/// Reads the API-key from the file or stdin, if requested via the commandline
+fn read_api_key(args: &LinkArgs) -> Result<Option<String>> {
+    let content = if let Some(path) = &args.api_key_file {
+        fs::read_to_string(path)
+            .with_context(|| format!("failed to read API-key from '{}'", path.display()))?
+    } else if args.api_key_stdin {
+        let mut buf = String::new();
+        io::stdin()
+            .read_to_string(&mut buf)
+            .context("failed to read API-key from stdin")?;
+        buf
+    } else {
+        return Ok(None);
+    };
+    let api_key = content.trim();
+    if api_key.is_empty() {
+        bail!(CliError::usage("API-key is empty"));
+    }
+    if let Err(e) = validate_api_key(api_key) {
+        bail!(CliError::usage(e));
+    }
+    Ok(Some(api_key.to_string()))
+}
+
+fn validate_api_key(input: &str) -> Result<(), &'static str> {
+    if input.find(char::is_whitespace).is_some() {
+        Err("whitespaces are not allowed in API-keys")
+    } else {
+        Ok(())
+    }
+}
+
+/// Applies the answer of the api-key prompt to the current key of a link
+///
+/// An empty answer keeps the current key, while `-` removes it.
+fn replace_api_key(current: Option<String>, input: String) -> Option<String> {
+    match input.as_str() {
+        "" => current,
+        REMOVE_API_KEY => None,
+        _ => Some(input),
+    }
+}
+
+/// Answer to the api-key prompt, that removes the key from the link
+const REMOVE_API_KEY: &str = "-";
+
+fn create_new(mut db: LinkDb, preset_key: Option<String>) -> Result<()> {
     info("Creating a new link...")?;
 
     // NOTE: This is not very efficient, but its good enough for now.
@@ -85,18 +472,13 @@ fn create_new(mut db: LinkDb) -> Result<()> {
         })
         .interact()?;
 
-    let api_key: String = input("Enter the API-key for the connection (leave empty if none):")
-        .placeholder("sk-d67e0cca1ab6d95f243")
-        .validate(|input: &String| {
-            if input.find(char::is_whitespace).is_some() {
-                Err("whitespaces are not allowed in API-keys")
-            } else {
-                Ok(())
-            }
-        })
-        .required(false)
-        .default_input("")
-        .interact()?;
+    let api_key = match preset_key {
+        Some(key) => key,
+        None => password("Enter the API-key for the connection (leave empty if none):")
+            .validate(|input: &String| validate_api_key(input))
+            .allow_empty()
+            .interact()?,
+    };
 
     let api_key = if api_key.is_empty() {
         None
@@ -104,7 +486,22 @@ fn create_new(mut db: LinkDb) -> Result<()> {
         Some(api_key)
     };
 
-    let new_link = Link { name, api, api_key };
+    let kind = query_kind(LinkKind::Node)?;
+    let (ca_cert, client_cert, client_key) = query_tls(None, None, None)?;
+    let environment = query_environment(None)?;
+
+    let mut new_link = Link {
+        name,
+        api,
+        api_key,
+        ca_cert,
+        client_cert,
+        client_key,
+        environment,
+        kind,
+        api_version: None,
+    };
+    new_link.api_version = negotiate_api_version(&new_link)?;
     info(new_link.to_string())?;
 
     // Save to db
@@ -115,7 +512,7 @@ fn create_new(mut db: LinkDb) -> Result<()> {
     Ok(())
 }
 
-fn modify_existing(mut db: LinkDb, link: Link) -> Result<()> {
+fn modify_existing(mut db: LinkDb, link: Link, preset_key: Option<String>) -> Result<()> {
     info(format!("Changing existing link {}", link))?;
     let delete = select("What do you want to do?")
         .item(true, "Delete link", "deletes the node from our database")
@@ -150,31 +547,39 @@ fn modify_existing(mut db: LinkDb, link: Link) -> Result<()> {
         .required(false)
         .interact()?;
 
-    let api_key: String =
-        input("Enter the API-key for the connection (leave empty to keep the current value):")
-            .placeholder(&link.api_key.clone().unwrap_or_default())
-            .validate(|input: &String| {
-                if input.find(char::is_whitespace).is_some() {
-                    Err("whitespaces are not allowed in API-keys")
-                } else {
-                    Ok(())
-                }
-            })
-            .default_input(&link.api_key.unwrap_or_default())
-            .required(false)
-            .interact()?;
-
-    let api_key = if api_key.is_empty() {
-        None
-    } else {
-        Some(api_key)
+    let api_key = match preset_key {
+        Some(key) => key,
+        None => password(
+            "Enter the API-key for the connection (leave empty to keep the current value, '-' to remove it):",
+        )
+        .validate(|input: &String| validate_api_key(input))
+        .allow_empty()
+        .interact()?,
     };
 
-    let new_link = Link {
+    let api_key = replace_api_key(link.api_key, api_key);
+
+    let kind = query_kind(link.kind)?;
+    let (ca_cert, client_cert, client_key) =
+        query_tls(link.ca_cert, link.client_cert, link.client_key)?;
+    let environment = query_environment(link.environment)?;
+
+    // Another address may point to a node with another api-version
+    let api_changed = api != link.api;
+    let mut new_link = Link {
         name: link.name.clone(),
         api,
         api_key,
+        ca_cert,
+        client_cert,
+        client_key,
+        environment,
+        kind,
+        api_version: link.api_version,
     };
+    if api_changed {
+        new_link.api_version = negotiate_api_version(&new_link)?;
+    }
 
     // Commit changes
     db.modify_link(&link.name, new_link)?;
@@ -183,3 +588,120 @@ fn modify_existing(mut db: LinkDb, link: Link) -> Result<()> {
     outro(format!("Modified link '{}'", link.name))?;
     Ok(())
 }
+
+/// Asks for the kind of service behind a link
+fn query_kind(current: LinkKind) -> Result<LinkKind> {
+    let kind = select("What kind of service do you want to link?")
+        .item(LinkKind::Node, "node", "contract- or agent-node")
+        .item(LinkKind::Registry, "registry", "package registry")
+        .initial_value(current)
+        .interact()?;
+    Ok(kind)
+}
+
+/// Asks for the environment label of a link
+fn query_environment(current: Option<Environment>) -> Result<Option<Environment>> {
+    let environment = select("Which environment does the node belong to?")
+        .item(None, "Unlabeled", "")
+        .item(Some(Environment::Dev), "dev", "development or test node")
+        .item(Some(Environment::Staging), "staging", "")
+        .item(
+            Some(Environment::Prod),
+            "prod",
+            "deployments print an additional warning",
+        )
+        .initial_value(current)
+        .interact()?;
+    Ok(environment)
+}
+
+type TlsPaths = (Option<PathBuf>, Option<PathBuf>, Option<PathBuf>);
+
+/// Asks for the (optional) CA certificate, client certificate and client key of a link
+fn query_tls(
+    ca_cert: Option<PathBuf>,
+    client_cert: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+) -> Result<TlsPaths> {
+    let has_tls = ca_cert.is_some() || client_cert.is_some();
+    if !confirm("Does the node require a custom CA or client certificates?")
+        .initial_value(has_tls)
+        .interact()?
+    {
+        return Ok((None, None, None));
+    }
+    let ca_cert = query_path("Path to the CA certificate (leave empty if none):", ca_cert)?;
+    let client_cert = query_path(
+        "Path to the client certificate (leave empty if none):",
+        client_cert,
+    )?;
+    let client_key = match client_cert {
+        Some(_) => query_path("Path to the client key:", client_key)?,
+        None => None,
+    };
+    Ok((ca_cert, client_cert, client_key))
+}
+
+/// Asks for the path to an existing file
+fn query_path(prompt: &str, current: Option<PathBuf>) -> Result<Option<PathBuf>> {
+    let current = current.map(|p| p.display().to_string()).unwrap_or_default();
+    let path: String = input(prompt)
+        .placeholder(&current)
+        .validate(|input: &String| {
+            if !input.is_empty() && !PathBuf::from(input).is_file() {
+                Err("file does not exist")
+            } else {
+                Ok(())
+            }
+        })
+        .default_input(&current)
+        .required(false)
+        .interact()?;
+    if path.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(PathBuf::from(path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_key_can_be_kept_replaced_or_removed() {
+        let current = || Some("old".to_string());
+        assert_eq!(replace_api_key(current(), String::new()), current());
+        assert_eq!(
+            replace_api_key(current(), "new".to_string()),
+            Some("new".to_string())
+        );
+        assert_eq!(replace_api_key(current(), "-".to_string()), None);
+        assert_eq!(replace_api_key(None, String::new()), None);
+    }
+
+    #[test]
+    fn link_details_mask_api_key() -> Result<()> {
+        let link = Link {
+            name: "node".to_string(),
+            api: "http://localhost:3000".parse()?,
+            api_key: Some("secret".to_string()),
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            environment: Some(Environment::Dev),
+            kind: LinkKind::Node,
+            api_version: Some("v1".to_string()),
+        };
+        let masked = serde_json::to_value(LinkDetails::new(link.clone(), false))?;
+        assert_eq!(masked["api_key"], MASKED);
+        assert_eq!(masked["environment"], "dev");
+        assert_eq!(masked["api_version"], "v1");
+
+        let details = LinkDetails::new(link, true);
+        assert!(details
+            .rows()
+            .contains(&["API-key".to_string(), "secret".to_string()]));
+        Ok(())
+    }
+}
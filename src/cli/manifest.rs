@@ -0,0 +1,110 @@
+use anyhow::{bail, Context, Result};
+use borderless_pkg::{PkgType, WasmPkg};
+
+use crate::{
+    error::CliError,
+    log::success,
+    manifest::parse_manifest,
+    package::read_package,
+    template::{Manifest, PkgInfo},
+    util::{confirm_write, ensure_file, write_atomic},
+    FromPackageArgs, ManifestCmd,
+};
+
+pub fn handle_manifest(cmd: ManifestCmd, assume_yes: bool) -> Result<()> {
+    match cmd {
+        ManifestCmd::FromPackage(args) => from_package(args, assume_yes),
+    }
+}
+
+/// Reconstructs the `Manifest.toml` of a package
+fn from_package(args: FromPackageArgs, assume_yes: bool) -> Result<()> {
+    ensure_file(&args.package, "package definition")?;
+    let package = read_package(&args.package)?;
+    let name = package.name.clone();
+    let version = package.source.version.to_string();
+
+    let manifest = format!(
+        "# Regenerated from package '{name}' {version}\n{}",
+        manifest_from_package(package)?
+    );
+
+    // Never produce a manifest, that 'borderless pack' cannot read
+    parse_manifest(&manifest).context("regenerated manifest is invalid")?;
+
+    let Some(output) = args.output else {
+        print!("{manifest}");
+        return Ok(());
+    };
+    confirm_write(&output, assume_yes)?;
+    write_atomic(&output, manifest.as_bytes())?;
+    success(format!(
+        "Wrote manifest of '{name}' to '{}'",
+        output.display()
+    ))?;
+    Ok(())
+}
+
+/// Maps the fields of a package back to the sections of a `Manifest.toml`
+fn manifest_from_package(package: WasmPkg) -> Result<String> {
+    let info = PkgInfo {
+        name: package.name,
+        app_name: package.app_name,
+        app_module: package.app_module,
+    };
+    let (agent, contract) = match package.pkg_type {
+        PkgType::Agent => (Some(info), None),
+        PkgType::Contract => (None, Some(info)),
+    };
+    if contract.is_some() && package.capabilities.is_some() {
+        bail!(CliError::usage(
+            "invalid package - contracts cannot have capabilities"
+        ));
+    }
+    let manifest = Manifest {
+        agent,
+        contract,
+        capabilities: package.capabilities,
+        meta: Some(package.meta),
+        build: None,
+    };
+    Ok(toml::to_string(&manifest)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borderless_cli::package::test_support;
+    use borderless_pkg::Capabilities;
+
+    #[test]
+    fn manifest_round_trip() -> Result<()> {
+        let package = WasmPkg {
+            name: "my-agent".to_string(),
+            app_name: Some("app".to_string()),
+            app_module: None,
+            capabilities: Some(Capabilities {
+                network: true,
+                websocket: false,
+                url_whitelist: vec!["https://example.com".to_string()],
+            }),
+            pkg_type: PkgType::Agent,
+            meta: borderless_pkg::PkgMeta {
+                license: Some("MIT".to_string()),
+                ..Default::default()
+            },
+            ..test_support::package()
+        };
+        let manifest = parse_manifest(&manifest_from_package(package)?)?;
+        assert!(manifest.contract.is_none());
+        let agent = manifest.agent.unwrap();
+        assert_eq!(agent.name, "my-agent");
+        assert_eq!(agent.app_name.as_deref(), Some("app"));
+        assert!(agent.app_module.is_none());
+        let capabilities = manifest.capabilities.unwrap();
+        assert!(capabilities.network);
+        assert_eq!(capabilities.url_whitelist, ["https://example.com"]);
+        assert_eq!(manifest.meta.unwrap().license.as_deref(), Some("MIT"));
+        Ok(())
+    }
+}
@@ -1,64 +1,73 @@
-use std::{
-    fs,
-    io::BufReader,
-    path::{Path, PathBuf},
-};
+use std::fs;
 
 use anyhow::{bail, Result};
-use borderless_pkg::WasmPkg;
-use cliclack::{confirm, intro, log::success};
-use serde::de::DeserializeOwned;
+use cliclack::intro;
 use serde_json::Value;
 
-use crate::config::get_config;
+use crate::{
+    log::{info, success},
+    package::{merge_package, read_package, validate_package},
+    util::{check_schema, confirm_creation, ensure_file, ensure_input, read_buffered},
+    MergeArgs,
+};
 
-pub fn handle_merge(introduction_path: PathBuf, package_path: PathBuf) -> Result<()> {
-    // Check that introduction exists and is a file
-    if !introduction_path.exists() {
-        bail!(
-            "failed to read introduction at '{}' - file does not exist",
-            introduction_path.display()
-        );
-    }
-    if !introduction_path.is_file() {
-        bail!("{} is not a file", introduction_path.display());
-    }
+pub fn handle_merge(args: MergeArgs, assume_yes: bool) -> Result<()> {
+    let introduction_path = args.introduction;
+    let package_path = args.package_json;
 
-    // Check that package exists and is a file
-    if !package_path.exists() {
-        bail!(
-            "failed to read package definition at '{}' - file does not exist",
-            introduction_path.display()
-        );
-    }
-    if !package_path.is_file() {
-        bail!("{} is not a file", package_path.display());
+    // Check that introduction and package exist and are files
+    ensure_file(&introduction_path, "introduction")?;
+    ensure_input(&package_path, "package definition")?;
+    if let Some(state_path) = &args.state {
+        ensure_file(state_path, "initial state")?;
     }
 
     intro("⟡ Merging package definition into introduction ...")?;
 
     let mut introduction: Value = read_buffered(&introduction_path)?;
 
+    // info(format!("Parsed introduction '{}'", introduction_path.display()))?;
+    let package = read_package(&package_path)?;
+    // info(format!("Parsed package '{}'", package_path.display()))?;
+    if args.validate_package {
+        validate_package(&package)?;
+        info(format!("Validated package '{}'", package.name))?;
+    }
+    merge_package(&mut introduction, package)?;
+
     if let Value::Object(map) = &mut introduction {
-        // info(format!("Parsed introduction '{}'", introduction_path.display()))?;
-        let package: WasmPkg = read_buffered(&package_path)?;
-        // info(format!("Parsed package '{}'", package_path.display()))?;
-        let pkg_value = serde_json::to_value(package)?;
-        map.insert("package".to_string(), pkg_value);
+        if let Some(state_path) = &args.state {
+            let state: Value = read_buffered(state_path)?;
+            map.insert("initial_state".to_string(), state);
+            info(format!(
+                "Replaced initial state with '{}'",
+                state_path.display()
+            ))?;
+        }
     } else {
         bail!("introduction must be a json-object");
     }
+    if let Some(schema_path) = &args.schema {
+        check_schema(schema_path, &introduction, "merged introduction")?;
+        info(format!(
+            "Validated introduction against '{}'",
+            schema_path.display()
+        ))?;
+    }
+
+    if args.dry_run {
+        println!("{}", serde_json::to_string_pretty(&introduction)?);
+        return Ok(());
+    }
 
     // Check, if creation and overwrite requires confirmation
-    if get_config().confirm_creation
-        && !confirm(format!(
+    confirm_creation(
+        format!(
             "This will overwrite the existing introduction at '{}'",
             introduction_path.display()
-        ))
-        .interact()?
-    {
-        bail!("Process aborted by user.");
-    }
+        ),
+        assume_yes,
+    )?;
 
     fs::write(&introduction_path, introduction.to_string())?;
 
@@ -70,9 +79,32 @@ pub fn handle_merge(introduction_path: PathBuf, package_path: PathBuf) -> Result
     Ok(())
 }
 
-fn read_buffered<S: DeserializeOwned>(path: &Path) -> Result<S> {
-    let file = fs::File::open(path)?;
-    let reader = BufReader::new(file);
-    let value = serde_json::from_reader(reader)?;
-    Ok(value)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TestDir;
+
+    #[test]
+    fn missing_package_reports_package_path() -> Result<()> {
+        let dir = TestDir::new("merge");
+        let introduction = dir.join("introduction.json");
+        let package = dir.join("package.json");
+        fs::write(&introduction, "{}")?;
+
+        let args = MergeArgs {
+            introduction: introduction.clone(),
+            package_json: package.clone(),
+            dry_run: true,
+            validate_package: false,
+            state: None,
+            schema: None,
+        };
+        let err = handle_merge(args, true).unwrap_err();
+        let msg = err.to_string();
+
+        assert!(msg.contains("package definition"), "{msg}");
+        assert!(msg.contains(&package.display().to_string()), "{msg}");
+        assert!(!msg.contains(&introduction.display().to_string()), "{msg}");
+        Ok(())
+    }
 }
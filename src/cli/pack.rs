@@ -1,36 +1,57 @@
 use anyhow::{bail, Context, Result};
 use borderless_hash::Hash256;
 use borderless_pkg::*;
-use cliclack::{
-    confirm, intro,
-    log::{info, success, warning},
-    spinner,
-};
-use convert_case::{Case, Casing};
-use git2::{DescribeFormatOptions, DescribeOptions, Repository, StatusOptions};
+use cliclack::{confirm, intro, spinner, ProgressBar};
+use git2::{DescribeFormatOptions, DescribeOptions, DiffOptions, Repository, StatusOptions};
 use git_info::GitInfo;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
 use std::{
+    env,
+    fmt::Display,
     fs,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Write},
     path::{Path, PathBuf},
     process::{Command, Stdio},
-    str::FromStr,
+    thread,
+    time::Instant,
+};
+
+use crate::{
+    build::{describe_difference, CargoOutput, CompiledUnit},
+    error::CliError,
+    interrupt::{self, ChildWatcher},
+    log::{error, info, record, success, warning},
+    manifest::{
+        cargo_metadata, check_crate_type, diverging_crate_name, manifest_from_metadata,
+        parse_manifest,
+    },
+    template::{package_schema, Manifest},
+    util::{confirm_creation, ensure_file, ensure_interactive, format_table, write_atomic},
+    wasm::strip_custom_sections,
+    PackArgs,
 };
 
-use crate::template::Manifest;
+pub fn handle_pack(args: PackArgs, assume_yes: bool) -> Result<()> {
+    if args.json_schema {
+        print!("{}", package_schema()?);
+        return Ok(());
+    }
 
-pub fn handle_pack(path: PathBuf) -> Result<()> {
-    let absolute_path = fs::canonicalize(&path).context("Failed to resolve absolute path")?;
+    let path = project_dir(&args)?;
+    let absolute_path = fs::canonicalize(&path)
+        .with_context(|| format!("Failed to resolve absolute path of '{}'", path.display()))?;
     if !absolute_path.is_dir() {
-        bail!("Not a directory: {}", absolute_path.display());
+        bail!(CliError::usage(format!(
+            "Not a directory: {}",
+            absolute_path.display()
+        )));
     }
 
     // Validate the project directory
     check_project_structure(&path)?;
 
     // Parse the manifest
-    let manifest = read_manifest(&path).context("failed to read Manifest.toml")?;
+    let manifest = read_manifest(&path)?;
     let (pkg_type, pkg_info) = match (manifest.agent, manifest.contract) {
         (Some(info), None) => {
             intro(format!("📦 Create package for agent '{}'", info.name))?;
@@ -51,25 +72,89 @@ pub fn handle_pack(path: PathBuf) -> Result<()> {
         absolute_path.display()
     ))?;
 
-    // Compile the project (this gives us the target path)
-    let target_path = compile_project(&absolute_path)?;
+    if let Some(since) = &args.since {
+        let changed = changed_since(&absolute_path, since)?;
+        if changed.is_empty() {
+            success(format!(
+                "No sources of '{}' changed since '{since}' - skipping the build",
+                pkg_info.name
+            ))?;
+            return Ok(());
+        }
+        let files: Vec<_> = changed.iter().map(|f| f.display().to_string()).collect();
+        info(format!(
+            "{} files changed since '{since}':\n{}",
+            files.len(),
+            files.join("\n")
+        ))?;
+    }
+
+    check_wasm_target(&absolute_path, wasm_target(&args))?;
+    // Features from the commandline replace the ones from the manifest
+    let features = match &args.features {
+        Some(features) => features.iter().filter(|f| !f.is_empty()).cloned().collect(),
+        None => manifest.build.unwrap_or_default().features,
+    };
+    if !features.is_empty() {
+        info(format!("Enabled features: {}", features.join(", ")))?;
+    }
+    let build_args = cargo_build_args(&args, &features);
+    if args.verify_reproducible {
+        return verify_reproducible(&absolute_path, &pkg_info.name, &build_args, args.keep_temp);
+    }
+
+    // Compile the project (this gives us the target path and the toolchain that was used)
+    let build = compile_project(&absolute_path, &build_args, None)?;
+    if args.deny_warnings && !build.warnings.is_empty() {
+        for rendered in &build.warnings {
+            warning(rendered)?;
+        }
+        bail!(CliError::build(format!(
+            "Build produced {} warnings ( denied by --deny-warnings )",
+            build.warnings.len()
+        )));
+    }
+    // Nothing is reported, if every crate was still fresh
+    if (args.timings || args.cargo_verbose > 0) && !build.units.is_empty() {
+        info(format!(
+            "Slowest crates ( time until cargo reported their artifact, not their compile time ):\n{}",
+            slowest_units(&build.units).trim_end()
+        ))?;
+    }
+    let toolchain = build.toolchain;
 
     // read wasm as bytes
-    let wasm_bytes = read_wasm_file(&target_path, &pkg_info.name)?;
+    let mut wasm_bytes = read_wasm_file(&build.wasm_path)?;
+    if args.strip {
+        let stripped = strip_custom_sections(&wasm_bytes).context("failed to strip wasm binary")?;
+        info(format!(
+            "Stripped custom sections, saved {}",
+            human_readable_size(wasm_bytes.len() - stripped.len())
+        ))?;
+        wasm_bytes = stripped;
+    }
 
-    // try to get git-info
-    let git_info = match get_git_info(&absolute_path) {
-        Ok(info) => {
+    // try to get git-info ( there is no need to ask, if we don't write the package anyway )
+    let repo_info = match get_git_info(&absolute_path) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warning(format!("Failed to obtain git-info: {e}"))?;
+            None
+        }
+    };
+    let git_info = match &repo_info {
+        Some(_) if args.check_only => None,
+        // With --yes the git-info is always added, since it is what a release build wants
+        Some(info) if assume_yes => Some(info.clone()),
+        Some(info) => {
+            ensure_interactive("pass --yes to add the git-info")?;
             if confirm(format!("Add git-info '{}' to package.json?", info)).interact()? {
-                Some(info)
+                Some(info.clone())
             } else {
                 None
             }
         }
-        Err(e) => {
-            warning(format!("Failed to obtain git-info: {e}"))?;
-            None
-        }
+        None => None,
     };
 
     // Create package
@@ -88,48 +173,322 @@ pub fn handle_pack(path: PathBuf) -> Result<()> {
                 git_info,
             },
         },
+    };
+    let digest = pkg.source.digest;
+    let out = if args.minify {
+        args.format.encode_minified(pkg)?
+    } else {
+        args.format.encode(pkg)?
+    };
+
+    // The lock is generated before anything is written, so we never leave a package without its lock behind
+    let lock = if args.lock {
+        let mut lock = PackageLock::generate(
+            &absolute_path,
+            pkg_info.name.clone(),
+            digest,
+            repo_info,
+            toolchain,
+        )?;
+        lock.message = args.message.clone();
+        if args.emit_manifest_digest {
+            let digest = manifest_digest(&path)?;
+            info(format!("Manifest digest = {}", String::from(digest)))?;
+            lock.manifest = Some(digest);
+        }
+        Some(lock)
+    } else {
+        None
+    };
+
+    if args.check_only {
+        success(format!(
+            "Package check passed for '{}', digest = {}, size = {}",
+            pkg_info.name,
+            String::from(digest),
+            human_readable_size(out.len())
+        ))?;
+        return Ok(());
     }
-    .into_dto();
-    let out = serde_json::to_vec(&pkg)?;
 
-    let pkg_file = path.join("package.json");
-    fs::write(&pkg_file, &out)?;
+    if args.stdout {
+        // All other output goes to stderr, so the package can be piped into other tools
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&out)?;
+        stdout.flush()?;
+        success(format!(
+            "Wrote package definition for '{}' to stdout",
+            pkg_info.name
+        ))?;
+    } else {
+        let pkg_file = path.join(args.format.file_name());
+        confirm_creation(
+            format!("Write package definition to '{}'?", pkg_file.display()),
+            assume_yes,
+        )?;
+        write_atomic(&pkg_file, &out)?;
 
-    success(format!(
-        "Created package definition for '{}', output = {}",
-        pkg_info.name,
-        pkg_file.display()
-    ))?;
+        success(format!(
+            "Created package definition for '{}', output = {}",
+            pkg_info.name,
+            pkg_file.display()
+        ))?;
+    }
+
+    if let Some(lock) = lock {
+        let lock_file = path.join("package.lock");
+        confirm_creation(
+            format!("Write lockfile to '{}'?", lock_file.display()),
+            assume_yes,
+        )?;
+        write_atomic(&lock_file, &serde_json::to_vec_pretty(&lock)?)?;
+        success(format!(
+            "Created lockfile, output = {}",
+            lock_file.display()
+        ))?;
+    }
     Ok(())
 }
 
+/// Provenance information about a package, that is written to `package.lock`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackageLock {
+    /// Name of the package
+    pub name: String,
+    /// Digest of the wasm binary
+    pub digest: Hash256,
+    /// State of the repository, when the package was built
+    pub git_info: Option<GitInfo>,
+    /// Toolchain that was used to compile the package
+    pub toolchain: Toolchain,
+    /// Digest of the `Cargo.lock` ( if the project has one )
+    pub cargo_lock: Option<Hash256>,
+    /// Release note, that was given via `--message`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Digest of the manifest, that was given via `--emit-manifest-digest`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Hash256>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Toolchain {
+    /// Output of `rustc --version`
+    pub rustc: String,
+    /// Output of `cargo --version`
+    pub cargo: String,
+    /// Active rustup toolchain ( if rustup is used )
+    #[serde(default)]
+    pub channel: Option<String>,
+}
+
+impl Toolchain {
+    /// Detects the toolchain, that cargo uses in the work directory
+    pub(super) fn detect(work_dir: &Path) -> Result<Self> {
+        // Without rustup there is no notion of a channel
+        let channel = tool_output(work_dir, "rustup", &["show", "active-toolchain"])
+            .ok()
+            .and_then(|out| out.split_whitespace().next().map(str::to_string));
+        Ok(Toolchain {
+            rustc: tool_output(work_dir, "rustc", &["--version"])?,
+            cargo: tool_output(work_dir, "cargo", &["--version"])?,
+            channel,
+        })
+    }
+}
+
+impl std::fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.rustc)?;
+        if let Some(channel) = &self.channel {
+            write!(f, " [{channel}]")?;
+        }
+        Ok(())
+    }
+}
+
+impl PackageLock {
+    fn generate(
+        work_dir: &Path,
+        name: String,
+        digest: Hash256,
+        git_info: Option<GitInfo>,
+        toolchain: Toolchain,
+    ) -> Result<Self> {
+        // The lockfile is either in the project or in the root of its workspace
+        let cargo_lock = match work_dir
+            .ancestors()
+            .map(|dir| dir.join("Cargo.lock"))
+            .find(|p| p.is_file())
+        {
+            Some(lock_path) => {
+                let bytes = fs::read(&lock_path)
+                    .with_context(|| format!("failed to read '{}'", lock_path.display()))?;
+                Some(Hash256::digest(&bytes))
+            }
+            None => None,
+        };
+        Ok(PackageLock {
+            name,
+            digest,
+            git_info,
+            toolchain,
+            cargo_lock,
+            message: None,
+            manifest: None,
+        })
+    }
+}
+
+/// Checks via rustup, if the given target is installed
+///
+/// Returns `None`, if rustup is not available.
+pub(super) fn wasm_target_installed(work_dir: &Path, target: &str) -> Option<bool> {
+    let installed = tool_output(work_dir, "rustup", &["target", "list", "--installed"]).ok()?;
+    Some(installed.lines().any(|t| t.trim() == target))
+}
+
+/// Checks that the target is a wasm target, that is installed for the toolchain of the project
+///
+/// Without rustup we cannot tell, so cargo has to report a missing target.
+fn check_wasm_target(work_dir: &Path, target: &str) -> Result<()> {
+    if !target.starts_with("wasm32-") {
+        bail!(CliError::usage(format!(
+            "'{target}' is not a wasm target ( e.g. {WASM_TARGET} or wasm32-wasip1 )"
+        )));
+    }
+    if wasm_target_installed(work_dir, target) == Some(false) {
+        bail!(CliError::usage(format!(
+            "target '{target}' is not installed - run `rustup target add {target}`"
+        )));
+    }
+    Ok(())
+}
+
+/// Runs `<tool> <args>` and returns its output
+///
+/// The tool is executed in the work directory, so a `rust-toolchain.toml` of the project is respected.
+fn tool_output(work_dir: &Path, tool: &str, args: &[&str]) -> Result<String> {
+    let cmd = format!("{tool} {}", args.join(" "));
+    let output = Command::new(tool)
+        .args(args)
+        .current_dir(work_dir)
+        .output()
+        .with_context(|| format!("Failed to run `{cmd}`"))?;
+    if !output.status.success() {
+        bail!("`{cmd}` exited with {}", output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the project directory, either given directly or by the path to one of its manifests
+fn project_dir(args: &PackArgs) -> Result<PathBuf> {
+    let Some(manifest_path) = &args.manifest_path else {
+        return Ok(args.project_path.clone());
+    };
+    ensure_file(manifest_path, "manifest")?;
+    let file_name = manifest_path.file_name().and_then(|n| n.to_str());
+    if !matches!(file_name, Some("Manifest.toml" | "Cargo.toml")) {
+        bail!(CliError::usage(format!(
+            "'{}' is not a manifest - expected a path to a Manifest.toml or Cargo.toml",
+            manifest_path.display()
+        )));
+    }
+    match manifest_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => Ok(dir.to_path_buf()),
+        _ => Ok(PathBuf::from(".")),
+    }
+}
+
 /// Validate the project structure
 fn check_project_structure(path: &Path) -> Result<()> {
     let cargo = path.join("Cargo.toml");
     let src = path.join("src");
     let lib = src.join("lib.rs");
-    let manifest = path.join("Manifest.toml");
-    let must_exist = [cargo, src, lib, manifest];
+    // The Manifest.toml is optional, since the manifest can also be part of the Cargo.toml
+    let must_exist = [&cargo, &src, &lib];
     for p in must_exist {
         if !p.exists() {
-            bail!("missing {} in project directory", p.display());
+            bail!(CliError::usage(format!(
+                "missing {} in project directory",
+                p.display()
+            )));
         }
     }
+    let content = fs::read_to_string(&cargo)
+        .with_context(|| format!("failed to read '{}'", cargo.display()))?;
+    check_crate_type(&content).with_context(|| format!("invalid '{}'", cargo.display()))?;
     Ok(())
 }
 
 /// Read the manifest from the project dir
+///
+/// The `Manifest.toml` takes precedence. Only if it does not exist,
+/// the manifest is read from the `[package.metadata.borderless]` section of the `Cargo.toml`.
 fn read_manifest(project_dir: &Path) -> Result<Manifest> {
     let manifest_path = project_dir.join("Manifest.toml");
-    let content = fs::read_to_string(&manifest_path)?;
-    let manifest: Manifest = toml::from_str(&content)?;
+    let cargo_path = project_dir.join("Cargo.toml");
+    let cargo_toml = fs::read_to_string(&cargo_path)
+        .with_context(|| format!("failed to read '{}'", cargo_path.display()))?;
+    let cargo_toml: toml::Table = toml::from_str(&cargo_toml)
+        .with_context(|| format!("failed to parse '{}'", cargo_path.display()))?;
+    let metadata = cargo_metadata(&cargo_toml);
+
+    if !manifest_path.exists() {
+        let Some(metadata) = metadata else {
+            bail!(CliError::usage(format!(
+                "missing {} in project directory ( or a [package.metadata.borderless] section in the Cargo.toml )",
+                manifest_path.display()
+            )));
+        };
+        return manifest_from_metadata(&cargo_toml, metadata.clone()).with_context(|| {
+            format!(
+                "invalid [package.metadata.borderless] in '{}'",
+                cargo_path.display()
+            )
+        });
+    }
+    if metadata.is_some() {
+        warning(
+            "Ignoring [package.metadata.borderless] in Cargo.toml, since a Manifest.toml exists",
+        )?;
+    }
+
+    let content = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("failed to read '{}'", manifest_path.display()))?;
+    let manifest = parse_manifest(&content)
+        .with_context(|| format!("invalid manifest '{}'", manifest_path.display()))?;
+    // The name in [package.metadata.borderless] is always the crate name, so only a Manifest.toml can diverge
+    if let Some(crate_name) = diverging_crate_name(&cargo_toml, &manifest) {
+        warning(format!(
+            "Package name in '{}' differs from the crate name '{crate_name}' in the Cargo.toml - is it the manifest of another crate?",
+            manifest_path.display()
+        ))?;
+    }
     Ok(manifest)
 }
 
+/// Digest of the file, that [`read_manifest`] reads the manifest from
+///
+/// Without a `Manifest.toml` this is the `Cargo.toml`, since the manifest is part of its metadata then.
+fn manifest_digest(project_dir: &Path) -> Result<Hash256> {
+    let manifest_path = project_dir.join("Manifest.toml");
+    let manifest_path = if manifest_path.exists() {
+        manifest_path
+    } else {
+        project_dir.join("Cargo.toml")
+    };
+    let content = fs::read(&manifest_path)
+        .with_context(|| format!("failed to read '{}'", manifest_path.display()))?;
+    Ok(Hash256::digest(&content))
+}
+
 fn get_version_from_cargo(path: &Path) -> Result<SemVer> {
     let manifest_path = path.join("Cargo.toml");
-    let content = fs::read_to_string(&manifest_path)?;
-    let manifest: cargo_toml::Manifest = toml::from_str(&content)?;
+    let content = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("failed to read '{}'", manifest_path.display()))?;
+    let manifest: cargo_toml::Manifest = toml::from_str(&content)
+        .with_context(|| format!("failed to parse '{}'", manifest_path.display()))?;
     manifest
         .package
         .context("missing [package] section in Cargo.toml")?
@@ -138,19 +497,10 @@ fn get_version_from_cargo(path: &Path) -> Result<SemVer> {
         .map_err(anyhow::Error::msg)
 }
 
-/// Reads the wasm binary from the target path
-fn read_wasm_file(target_dir: &Path, pkg_name: &str) -> Result<Vec<u8>> {
-    let wasm_pkg_name = format!("{}.wasm", pkg_name.to_case(Case::Snake));
-
-    // The target directory was obtained from cargo metadata.
-    //
-    // If `compile_project` was executed without errors before this function,
-    // we should always find a binary at this path:
-    let wasm_path = target_dir
-        .join("wasm32-unknown-unknown/release")
-        .join(wasm_pkg_name);
-
-    // Nonetheless: Check for existence of the binary
+/// Reads the wasm binary that was reported by cargo
+fn read_wasm_file(wasm_path: &Path) -> Result<Vec<u8>> {
+    // Cargo reported this path as build artifact,
+    // nonetheless: Check for existence of the binary
     if !wasm_path.exists() {
         bail!(
             "Failed to find wasm binary: '{}' does not exist",
@@ -159,12 +509,12 @@ fn read_wasm_file(target_dir: &Path, pkg_name: &str) -> Result<Vec<u8>> {
     }
 
     // Read bytes from disk
-    let wasm_bytes = fs::read(&wasm_path)
+    let wasm_bytes = fs::read(wasm_path)
         .with_context(|| format!("Failed to read WASM file: {}", wasm_path.display()))?;
 
     let wasm_file = wasm_path
         .file_name()
-        .unwrap_or_else(|| wasm_path.as_os_str())
+        .unwrap_or(wasm_path.as_os_str())
         .to_string_lossy();
 
     info(format!(
@@ -190,23 +540,187 @@ fn human_readable_size(size: usize) -> String {
     format!("{:.2} {}", size, units[unit_index])
 }
 
-/// Compiles the project into a wasm binary and returns the target path
-fn compile_project(work_dir: &Path) -> Result<PathBuf> {
-    let sp = spinner();
+/// Builds the project twice in fresh target directories and compares the resulting binaries
+///
+/// With `keep_temp`, the target directories are not removed afterwards, so they can be inspected.
+fn verify_reproducible(
+    work_dir: &Path,
+    pkg_name: &str,
+    build_args: &[String],
+    keep_temp: bool,
+) -> Result<()> {
+    let mut builds = Vec::new();
+    for run in 1..=2 {
+        let target_dir = env::temp_dir().join(format!(
+            "borderless-reproducible-{}-{run}",
+            std::process::id()
+        ));
+        info(format!("Build {run} of 2 in '{}'", target_dir.display()))?;
+        let result = compile_project(work_dir, build_args, Some(&target_dir))
+            .and_then(|build| read_wasm_file(&build.wasm_path));
+        // Clean up, even if the build failed ( unless the directories are kept for debugging )
+        if keep_temp {
+            info(format!("Kept target directory '{}'", target_dir.display()))?;
+        } else {
+            let _ = fs::remove_dir_all(&target_dir);
+        }
+        builds.push(result?);
+    }
+
+    let (first, second) = (&builds[0], &builds[1]);
+    let (digest_1, digest_2) = (Hash256::digest(first), Hash256::digest(second));
+    if digest_1 == digest_2 {
+        success(format!(
+            "Build of '{pkg_name}' is reproducible, digest = {}",
+            String::from(digest_1)
+        ))?;
+        return Ok(());
+    }
+
+    warning(format!(
+        "Digests differ: {} vs {}\n{}",
+        String::from(digest_1),
+        String::from(digest_2),
+        describe_difference(first, second)
+    ))?;
+    bail!(CliError::build(format!(
+        "Build of '{pkg_name}' is not reproducible"
+    )))
+}
+
+/// Default target triple of all packages
+pub(super) const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// Returns the target triple, that the project is compiled for
+fn wasm_target(args: &PackArgs) -> &str {
+    args.wasm_target.as_deref().unwrap_or(WASM_TARGET)
+}
+
+/// Assembles the arguments for `cargo build`
+///
+/// Cargo reports the path of the wasm binary, so the artifact is found for any target.
+fn cargo_build_args(args: &PackArgs, features: &[String]) -> Vec<String> {
+    let mut build_args = vec![
+        "build".to_string(),
+        "--release".to_string(),
+        format!("--target={}", wasm_target(args)),
+        "--message-format=json".to_string(),
+    ];
+    if !features.is_empty() {
+        build_args.push(format!("--features={}", features.join(",")));
+    }
+    if args.locked {
+        build_args.push("--locked".to_string());
+    }
+    if args.frozen {
+        build_args.push("--frozen".to_string());
+    }
+    match args.cargo_verbose {
+        0 => (),
+        1 => build_args.push("-v".to_string()),
+        _ => build_args.push("-vv".to_string()),
+    }
+    build_args
+}
+
+/// Result of a successful build
+struct Build {
+    /// Path of the compiled wasm binary
+    wasm_path: PathBuf,
+    /// Toolchain that was used
+    toolchain: Toolchain,
+    /// Rendered compiler warnings
+    warnings: Vec<String>,
+    /// Crates that were compiled ( and not reused from a previous build )
+    units: Vec<CompiledUnit>,
+}
+
+/// Shows the progress of cargo, either condensed into a spinner or as full output
+struct BuildProgress {
+    spinner: Option<ProgressBar>,
+    verbose: bool,
+}
+
+impl BuildProgress {
+    fn start(command: String, verbose: bool) -> Result<Self> {
+        if verbose {
+            info(format!("Running {command}"))?;
+            return Ok(BuildProgress {
+                spinner: None,
+                verbose,
+            });
+        }
+        let sp = spinner();
+        sp.start(command);
+        Ok(BuildProgress {
+            spinner: Some(sp),
+            verbose,
+        })
+    }
+
+    /// Handles a line of output from cargo
+    fn output(&self, line: &str) {
+        match &self.spinner {
+            Some(sp) => sp.set_message(line),
+            None => eprintln!("{line}"),
+        }
+    }
+
+    fn stop(&self, message: impl Display) -> io::Result<()> {
+        match &self.spinner {
+            Some(sp) => {
+                sp.stop(message);
+                Ok(())
+            }
+            None => info(message),
+        }
+    }
+
+    fn cancel(&self, message: impl Display) -> io::Result<()> {
+        match &self.spinner {
+            Some(sp) => {
+                sp.cancel(message);
+                Ok(())
+            }
+            None => warning(message),
+        }
+    }
+}
+
+/// Compiles the project into a wasm binary
+///
+/// If no target directory is given, cargo decides where to put the build artifacts.
+fn compile_project(
+    work_dir: &Path,
+    build_args: &[String],
+    target_dir: Option<&Path>,
+) -> Result<Build> {
+    let toolchain = Toolchain::detect(work_dir)?;
+    info(format!("Using toolchain: {toolchain}"))?;
+    record("CARGO", &toolchain.cargo);
+
+    // Verbose builds stream the full output of cargo instead of condensing it into the spinner
+    let verbose = build_args
+        .iter()
+        .any(|arg| matches!(arg.as_str(), "-v" | "-vv"));
 
     info("Compiling package to WebAssembly...")?;
-    sp.start("cargo build --release --target=wasm32-unknown-unknown");
+    // Ctrl-C stops cargo and the spinner, instead of leaving both behind
+    let _guard = interrupt::catch();
+    let sp = BuildProgress::start(format!("cargo {}", build_args.join(" ")), verbose)?;
 
     // Spawn `cargo build ...` with stdout/stderr piped.
     //
-    // NOTE: Cargo pipes its output to stderr and not to stdout
+    // NOTE: Cargo pipes its progress to stderr, the json messages go to stdout
     let mut child = Command::new("cargo")
-        .args(["build", "--release", "--target=wasm32-unknown-unknown"])
+        .args(build_args)
+        .envs(target_dir.map(|dir| ("CARGO_TARGET_DIR", dir)))
         .current_dir(work_dir)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .context("Failed to start `cargo build`")?;
+    let watcher = ChildWatcher::start(child.id());
 
     let stdout = child
         .stdout
@@ -217,46 +731,95 @@ fn compile_project(work_dir: &Path) -> Result<PathBuf> {
         .take()
         .context("Failed to capture stderr of cargo")?;
 
-    // Wrap stdout in a line‐buffered reader:
-    let mut _stdout_reader = BufReader::new(stdout).lines();
+    // The json messages are collected in the background, so neither pipe can fill up
+    let start = Instant::now();
+    let stdout_reader = thread::spawn(move || {
+        let mut output = CargoOutput::default();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            output.process_line(&line, start.elapsed());
+        }
+        output
+    });
     let stderr_reader = BufReader::new(stderr).lines();
 
     // Read lines from stderr as they arrive and update spinner
     for line_res in stderr_reader {
         let line = line_res.unwrap_or_else(|e| format!("failed to read cargo output: {e}"));
-        sp.set_message(&line);
+        record("CARGO", &line);
+        sp.output(&line);
     }
 
     // Wait for the child to exit, so we can check exit status.
+    watcher.stop();
     let status = child.wait().context("Failed to wait for cargo to finish")?;
+    let diagnostics = stdout_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Failed to read the output of cargo"))?;
+    if interrupt::interrupted() {
+        sp.cancel("Build interrupted")?;
+        bail!(CliError::interrupted("build interrupted"));
+    }
+    for rendered in diagnostics.warnings.iter().chain(&diagnostics.errors) {
+        record("CARGO", rendered);
+    }
+
+    if sp.verbose {
+        for rendered in &diagnostics.warnings {
+            warning(rendered)?;
+        }
+    }
 
     if !status.success() {
-        sp.stop("Build failed");
-        // If you also want stderr details, you can decode `output.stderr`:
-        // let stderr_text = String::from_utf8_lossy(&output.stderr);
-        bail!("WASM build failed",);
+        sp.stop("Build failed")?;
+        for rendered in &diagnostics.errors {
+            error(rendered)?;
+        }
+        bail!(CliError::build("WASM build failed"));
     }
 
-    // Now obtain the cargo metadata to retrieve the compilation path
-    sp.set_message("Reading cargo metadata...");
-    let output = Command::new("cargo")
-        .args(["metadata", "--no-deps", "--format-version=1"])
-        .current_dir(work_dir)
-        .output()
-        .context("Failed to run `cargo metadata`")?;
-    let metadata: Value = serde_json::from_slice(&output.stdout)
-        .context("failed to read output of `cargo metadata`")?;
+    // The package itself is built last, so its binary is the last reported artifact
+    let Some(wasm_path) = diagnostics.wasm_artifacts.last().cloned() else {
+        sp.stop("Build failed")?;
+        bail!(CliError::build(
+            "cargo did not produce a wasm binary ( is the crate-type set to \"cdylib\"? )"
+        ));
+    };
 
-    let target_path = metadata
-        .get("target_directory")
-        .and_then(|v| v.as_str())
-        .and_then(|s| PathBuf::from_str(s).ok())
-        .unwrap_or_else(|| work_dir.join("target"))
-        .canonicalize()?;
+    let summary = format!(
+        "in {:.1}s ( {} crates compiled )",
+        start.elapsed().as_secs_f64(),
+        diagnostics.units.len()
+    );
+    if diagnostics.warnings.is_empty() {
+        sp.stop(format!("WASM build completed successfully {summary}"))?;
+    } else {
+        sp.stop(format!(
+            "Build succeeded with {} warnings {summary}",
+            diagnostics.warnings.len()
+        ))?;
+    }
 
-    sp.stop("WASM build completed successfully.");
+    Ok(Build {
+        wasm_path,
+        toolchain,
+        warnings: diagnostics.warnings,
+        units: diagnostics.units,
+    })
+}
+
+/// Number of crates that are listed by [`slowest_units`]
+const SLOWEST_UNITS: usize = 5;
 
-    Ok(target_path)
+/// Returns a table of the crates, that took the longest until their artifact was reported
+fn slowest_units(units: &[CompiledUnit]) -> String {
+    let mut units = units.to_vec();
+    units.sort_by_key(|unit| std::cmp::Reverse(unit.duration));
+    let rows: Vec<_> = units
+        .into_iter()
+        .take(SLOWEST_UNITS)
+        .map(|unit| [unit.name, format!("{:.1}s", unit.duration.as_secs_f64())])
+        .collect();
+    format_table(["CRATE", "TIME UNTIL ARTIFACT"], &rows)
 }
 
 /// Opens the repository at `path` (usually `"."`) and returns a `GitInfo` with:
@@ -341,3 +904,190 @@ pub fn get_git_info(path: &Path) -> Result<GitInfo> {
 
     Ok(info)
 }
+
+/// Files of the project, that are relevant for the build
+const SOURCE_PATHS: [&str; 3] = ["src", "Cargo.toml", "Manifest.toml"];
+
+/// Returns the source files of the project, that changed since the given git reference
+///
+/// This includes committed, staged and uncommitted changes, as well as new untracked files.
+/// The paths are relative to the root of the repository.
+fn changed_since(project_dir: &Path, git_ref: &str) -> Result<Vec<PathBuf>> {
+    let repo = Repository::discover(project_dir)?;
+    let workdir = repo
+        .workdir()
+        .context("cannot compare against a bare repository")?;
+    let project_dir = fs::canonicalize(project_dir)?;
+    let relative = project_dir
+        .strip_prefix(fs::canonicalize(workdir)?)
+        .with_context(|| format!("'{}' is not part of the repository", project_dir.display()))?;
+
+    let tree = repo
+        .revparse_single(git_ref)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| {
+            CliError::usage(format!(
+                "invalid git reference '{git_ref}' - {}",
+                e.message()
+            ))
+        })?;
+
+    let mut opts = DiffOptions::new();
+    opts.include_untracked(true).recurse_untracked_dirs(true);
+    for path in SOURCE_PATHS {
+        opts.pathspec(relative.join(path));
+    }
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut opts))?;
+    let changed = diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+        .map(Path::to_path_buf)
+        .collect();
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TestDir;
+    use clap::Parser;
+    use std::time::Duration;
+
+    fn pack_args(args: &[&str]) -> PackArgs {
+        let cli = crate::Cli::parse_from(["borderless", "pack"].iter().chain(args));
+        match cli.command {
+            crate::Commands::Pack(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn project_dir_from_manifest_path() -> Result<()> {
+        let dir = TestDir::new("pack");
+        let manifest = dir.join("Manifest.toml");
+        let other = dir.join("notes.toml");
+        fs::write(&manifest, "")?;
+        fs::write(&other, "")?;
+
+        let args = pack_args(&["--manifest-path", manifest.to_str().unwrap()]);
+        assert_eq!(project_dir(&args)?, dir.to_path_buf());
+        assert_eq!(
+            project_dir(&pack_args(&["some/dir"]))?,
+            Path::new("some/dir")
+        );
+
+        let err = project_dir(&pack_args(&["--manifest-path", other.to_str().unwrap()]));
+        assert!(err.unwrap_err().to_string().contains("is not a manifest"));
+        let err = project_dir(&pack_args(&["--manifest-path", "missing/Cargo.toml"]));
+        assert!(err.unwrap_err().to_string().contains("does not exist"));
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_digests() -> Result<()> {
+        let dir = TestDir::new("digest");
+        fs::write(dir.join("Cargo.toml"), "[package]")?;
+        let cargo_digest = manifest_digest(&dir)?;
+        assert_eq!(cargo_digest, Hash256::digest(b"[package]"));
+
+        // The Manifest.toml takes precedence, and every change of it changes the digest
+        fs::write(dir.join("Manifest.toml"), "[contract]")?;
+        let digest = manifest_digest(&dir)?;
+        assert_ne!(digest, cargo_digest);
+        fs::write(dir.join("Manifest.toml"), "[contract] ")?;
+        assert_ne!(manifest_digest(&dir)?, digest);
+        Ok(())
+    }
+
+    #[test]
+    fn features_keep_the_project_dir() -> Result<()> {
+        let args = pack_args(&["--features", "std,logging", "some/dir"]);
+        assert_eq!(
+            args.features.as_deref(),
+            Some(&["std".to_string(), "logging".to_string()][..])
+        );
+        assert_eq!(project_dir(&args)?, Path::new("some/dir"));
+        Ok(())
+    }
+
+    #[test]
+    fn wasm_target_args() {
+        let build_args = cargo_build_args(&pack_args(&[]), &[]);
+        assert!(build_args.contains(&"--target=wasm32-unknown-unknown".to_string()));
+        assert!(!build_args.iter().any(|arg| arg.starts_with("--features")));
+        let features = ["std".to_string(), "logging".to_string()];
+        let build_args = cargo_build_args(&pack_args(&[]), &features);
+        assert!(build_args.contains(&"--features=std,logging".to_string()));
+        let build_args = cargo_build_args(
+            &pack_args(&[
+                "--wasm-target",
+                "wasm32-wasip1",
+                "--cargo-verbose",
+                "--cargo-verbose",
+            ]),
+            &[],
+        );
+        assert!(build_args.contains(&"--target=wasm32-wasip1".to_string()));
+        assert_eq!(build_args.last().map(String::as_str), Some("-vv"));
+
+        let err = check_wasm_target(Path::new("."), "x86_64-unknown-linux-gnu").unwrap_err();
+        assert!(err.to_string().contains("not a wasm target"), "{err}");
+    }
+
+    #[test]
+    fn changed_sources() -> Result<()> {
+        let dir = TestDir::new("since");
+        let project = dir.join("contracts/my-contract");
+        fs::create_dir_all(project.join("src"))?;
+        fs::write(project.join("Cargo.toml"), "[package]")?;
+        fs::write(project.join("src/lib.rs"), "")?;
+        fs::write(dir.join("README.md"), "")?;
+
+        let repo = Repository::init(&dir)?;
+        let mut index = repo.index()?;
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let sig = git2::Signature::now("test", "test@example.com")?;
+        let commit = repo.commit(Some("HEAD"), &sig, &sig, "release", &tree, &[])?;
+        repo.tag_lightweight("v0.1.0", &repo.find_object(commit, None)?, false)?;
+
+        assert!(changed_since(&project, "v0.1.0")?.is_empty());
+        // Files outside of the project sources are ignored
+        fs::write(dir.join("README.md"), "changed")?;
+        fs::write(project.join("notes.txt"), "")?;
+        assert!(changed_since(&project, "v0.1.0")?.is_empty());
+
+        fs::write(project.join("src/lib.rs"), "// changed")?;
+        fs::write(project.join("src/new.rs"), "")?;
+        let mut changed = changed_since(&project, "v0.1.0")?;
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                PathBuf::from("contracts/my-contract/src/lib.rs"),
+                PathBuf::from("contracts/my-contract/src/new.rs")
+            ]
+        );
+
+        let err = changed_since(&project, "v9.9.9").unwrap_err();
+        assert!(err.to_string().contains("invalid git reference"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn slowest_crates() {
+        let units: Vec<_> = (1..=7)
+            .map(|secs| CompiledUnit {
+                name: format!("crate-{secs}"),
+                duration: Duration::from_millis(secs * 1000),
+            })
+            .collect();
+        let table = slowest_units(&units);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), SLOWEST_UNITS + 1);
+        assert!(lines[0].contains("TIME UNTIL ARTIFACT"));
+        assert!(lines[1].starts_with("crate-7 ") && lines[1].ends_with(" 7.0s"));
+        assert!(!table.contains("crate-2"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use borderless_pkg::{semver::SemVer, WasmPkg};
+use cliclack::{intro, outro};
+
+use crate::{
+    api::{Node, RegistryPkg},
+    error::CliError,
+    log::info,
+    package::{check_digest, validate_package},
+    util::{confirm_write, write_atomic},
+    PullArgs,
+};
+
+pub fn handle_pull(args: PullArgs, assume_yes: bool) -> Result<()> {
+    let (name, version) = parse_package_spec(&args.package)?;
+
+    intro(format!("📥 Downloading package '{name}' ..."))?;
+    let registry = Node::select_registry()?;
+    // The listing is also needed for the digest, that the registry announces for the version
+    let listing = registry.registry_packages()?;
+
+    let version = match version {
+        Some(version) => version,
+        None => {
+            let version = latest_version(&listing, name).ok_or_else(|| {
+                CliError::usage(format!(
+                    "package '{name}' does not exist in '{}'",
+                    registry.link().name
+                ))
+            })?;
+            info(format!("Latest version of '{name}' is {version}"))?;
+            version
+        }
+    };
+
+    let package = registry.registry_package(name, &version.to_string())?;
+    if package.name != name || package.source.version != version {
+        bail!(CliError::network(format!(
+            "registry returned '{}@{}' instead of '{name}@{version}'",
+            package.name, package.source.version
+        )));
+    }
+    // Never store a package, whose code does not match its digest
+    validate_package(&package).with_context(|| format!("refusing to store '{name}@{version}'"))?;
+    // A consistent, but different build must not pass as the listed one
+    check_listed_digest(&listing, &package)?;
+    if let Some(expected) = &args.expected_digest {
+        check_digest(&package, expected)
+            .with_context(|| format!("refusing to store '{name}@{version}'"))?;
+    }
+    let digest = package.source.digest;
+
+    let output = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(args.format.file_name()));
+    confirm_write(&output, assume_yes)?;
+    write_atomic(&output, &args.format.encode(package)?)?;
+
+    outro(format!(
+        "Downloaded '{name}@{version}', digest = {}, output = {}",
+        String::from(digest),
+        output.display()
+    ))?;
+    Ok(())
+}
+
+/// Splits `<name>@<version>` into the name and the ( optional ) version
+fn parse_package_spec(spec: &str) -> Result<(&str, Option<SemVer>)> {
+    let (name, version) = match spec.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (spec, None),
+    };
+    if name.is_empty() {
+        bail!(CliError::usage(format!(
+            "invalid package '{spec}' - expected <name>@<version>"
+        )));
+    }
+    let version = version
+        .map(|v| {
+            v.parse::<SemVer>()
+                .map_err(|e| CliError::usage(format!("invalid version '{v}' - {e}")))
+        })
+        .transpose()?;
+    Ok((name, version))
+}
+
+/// Returns the highest version of a package in the registry
+fn latest_version(packages: &[RegistryPkg], name: &str) -> Option<SemVer> {
+    packages
+        .iter()
+        .filter(|pkg| pkg.name == name)
+        .filter_map(|pkg| pkg.version.as_deref()?.parse::<SemVer>().ok())
+        .max_by_key(|v| (v.major, v.minor, v.patch))
+}
+
+/// Compares the digest of a downloaded package with the digest in the listing of the registry
+///
+/// Registries are not required to list digests; without one there is nothing to compare.
+fn check_listed_digest(listing: &[RegistryPkg], package: &WasmPkg) -> Result<()> {
+    let version = package.source.version.to_string();
+    let listed = listing
+        .iter()
+        .find(|pkg| pkg.name == package.name && pkg.version.as_deref() == Some(version.as_str()))
+        .and_then(|pkg| pkg.digest.as_deref());
+    let Some(listed) = listed else {
+        return Ok(());
+    };
+    let actual = String::from(package.source.digest);
+    if !listed.trim().eq_ignore_ascii_case(&actual) {
+        bail!(CliError::network(format!(
+            "registry lists digest {listed} for '{}@{version}', but served a package with digest {actual}",
+            package.name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_specs() -> Result<()> {
+        let (name, version) = parse_package_spec("my-contract@1.2.3")?;
+        assert_eq!(name, "my-contract");
+        assert_eq!(version, Some("1.2.3".parse().map_err(anyhow::Error::msg)?));
+        assert_eq!(parse_package_spec("my-contract")?, ("my-contract", None));
+        assert!(parse_package_spec("@1.2.3").is_err());
+        assert!(parse_package_spec("my-contract@latest").is_err());
+
+        let pkg = |name: &str, version: &str| RegistryPkg {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            digest: None,
+        };
+        let packages = [
+            pkg("my-contract", "0.9.0"),
+            pkg("my-contract", "0.10.0"),
+            pkg("other", "2.0.0"),
+        ];
+        let latest = latest_version(&packages, "my-contract").map(|v| v.to_string());
+        assert_eq!(latest.as_deref(), Some("0.10.0"));
+        assert!(latest_version(&packages, "missing").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn listed_digests() -> Result<()> {
+        use borderless_cli::package::test_support;
+        use borderless_hash::Hash256;
+
+        let package = WasmPkg {
+            name: "my-contract".to_string(),
+            ..test_support::package()
+        };
+        let listed = |version: &str, digest: Option<String>| RegistryPkg {
+            name: "my-contract".to_string(),
+            version: Some(version.to_string()),
+            digest,
+        };
+        let actual = String::from(package.source.digest);
+
+        // Only the digest of the same version is compared
+        let other = String::from(Hash256::zero());
+        check_listed_digest(&[listed("0.1.0", None)], &package)?;
+        check_listed_digest(&[listed("0.2.0", Some(other.clone()))], &package)?;
+        check_listed_digest(
+            &[listed("0.1.0", Some(actual.to_ascii_uppercase()))],
+            &package,
+        )?;
+
+        let err = check_listed_digest(&[listed("0.1.0", Some(other))], &package).unwrap_err();
+        assert!(err.to_string().contains("but served a package"), "{err}");
+        assert_eq!(
+            crate::error::ErrorKind::of(&err),
+            crate::error::ErrorKind::Network
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use cliclack::{intro, outro};
+
+use crate::{
+    api::Node, log::info, util::format_table, ListPackagesArgs, OutputFormat, RegistryCmd,
+};
+
+pub fn handle_registry(cmd: RegistryCmd) -> Result<()> {
+    match cmd {
+        RegistryCmd::ListPackages(args) => list_packages(args),
+    }
+}
+
+fn list_packages(args: ListPackagesArgs) -> Result<()> {
+    if args.format == OutputFormat::Text {
+        intro("📚 Query packages of registry")?;
+    }
+    let registry = Node::select_registry()?;
+    let packages = registry.registry_packages()?;
+
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&packages)?);
+        return Ok(());
+    }
+    if packages.is_empty() {
+        outro(format!(
+            "No packages published in '{}'",
+            registry.link().name
+        ))?;
+        return Ok(());
+    }
+    info(format!(
+        "{} packages published in '{}'",
+        packages.len(),
+        registry.link().name
+    ))?;
+
+    let rows: Vec<[String; 3]> = packages
+        .into_iter()
+        .map(|pkg| {
+            [
+                pkg.name,
+                pkg.version.unwrap_or_else(|| "-".to_string()),
+                pkg.digest.unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    // The table is printed to stdout, so it can be processed further
+    print!("{}", format_table(["NAME", "VERSION", "DIGEST"], &rows));
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use borderless_pkg::PkgType;
+use cliclack::{intro, outro};
+
+use crate::{api::Node, log::info, util::format_table};
+
+pub fn handle_status() -> Result<()> {
+    intro("📋 Query deployed contracts and agents")?;
+    let node = Node::select()?;
+
+    let packages = node.deployed_packages()?;
+    if packages.is_empty() {
+        outro(format!(
+            "No contracts or agents deployed on '{}'",
+            node.link().name
+        ))?;
+        return Ok(());
+    }
+    info(format!(
+        "{} contracts or agents deployed on '{}'",
+        packages.len(),
+        node.link().name
+    ))?;
+
+    let rows: Vec<[String; 5]> = packages
+        .into_iter()
+        .map(|pkg| {
+            let pkg_type = match pkg.pkg_type {
+                PkgType::Contract => "contract",
+                PkgType::Agent => "agent",
+            };
+            [
+                pkg.id,
+                pkg_type.to_string(),
+                pkg.name.unwrap_or_else(|| "-".to_string()),
+                pkg.version.unwrap_or_else(|| "-".to_string()),
+                pkg.state_hash.unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    // The table is printed to stdout, so it can be processed further
+    print!(
+        "{}",
+        format_table(["ID", "TYPE", "NAME", "VERSION", "STATE-HASH"], &rows)
+    );
+    Ok(())
+}
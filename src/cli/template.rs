@@ -1,20 +1,26 @@
-use std::fs;
+use std::{fs, path::Path};
 
-use anyhow::Result;
-use borderless::common::Description;
-use cliclack::{confirm, intro, log::info, multiselect, outro};
+use anyhow::{bail, Context, Result};
+use borderless::{common::Description, BorderlessId};
+use cliclack::{confirm, intro, multiselect, outro};
 use serde_json::{json, Value};
 
-use crate::{api::Node, TemplateCmd};
+use crate::{
+    api::Node,
+    error::CliError,
+    log::{info, warning},
+    util::{ensure_file, ensure_interactive},
+    IntroductionArgs, TemplateCmd,
+};
 
-pub fn handle_template(cmd: TemplateCmd) -> Result<()> {
+pub fn handle_template(cmd: TemplateCmd, assume_yes: bool) -> Result<()> {
     match cmd {
-        TemplateCmd::Introduction => create_introduction()?,
+        TemplateCmd::Introduction(args) => create_introduction(args, assume_yes)?,
     }
     Ok(())
 }
 
-fn create_introduction() -> Result<()> {
+fn create_introduction(args: IntroductionArgs, assume_yes: bool) -> Result<()> {
     intro("Create new introduction template...")?;
 
     info("We establish a connection to a node to query for participants")?;
@@ -24,14 +30,14 @@ fn create_introduction() -> Result<()> {
     let info_pretty = serde_json::to_string_pretty(&node_info)?;
     info(format!("Node-Info:\n{info_pretty}"))?;
 
-    let peers = node.network_peers()?;
-
-    let mut participants = multiselect("Select peers for contract");
-
-    for (name, id) in peers {
-        participants = participants.item(id, format!("{} - {}", name, id), "");
+    let use_cache = args.use_cached_peers && !args.refresh_peers;
+    let participants = match &args.participants_file {
+        Some(path) => read_participants(&node, path, use_cache)?,
+        None => select_participants(&node, args.filter.as_deref(), use_cache)?,
+    };
+    if participants.is_empty() {
+        warning("No participants selected - add them to the introduction before deploying")?;
     }
-    let participants = participants.filter_mode().interact()?;
 
     let desc = Description {
         display_name: "".to_string(),
@@ -45,23 +51,105 @@ fn create_introduction() -> Result<()> {
         "roles": [],
         "sinks": [],
         "desc": desc,
-        "package": empty_obj(),
+        // The package is merged separately via `borderless merge`
+        "package": Value::Null,
     });
 
     let out_string = serde_json::to_string_pretty(&out)?;
 
-    if confirm("Save as 'introduction.json' ?").interact()? {
-        fs::write("./introduction.json", &out_string)?;
+    let output = &args.output;
+    if !assume_yes {
+        ensure_interactive(format!("pass --yes to save as '{}'", output.display()))?;
+    }
+    if assume_yes || confirm(format!("Save as '{}' ?", output.display())).interact()? {
+        if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create '{}'", parent.display()))?;
+        }
+        fs::write(output, &out_string)
+            .with_context(|| format!("failed to write '{}'", output.display()))?;
+        info(format!(
+            "Saved introduction template to '{}'",
+            output.display()
+        ))?;
     } else {
         info("Template:")?;
         println!("{out_string}");
     }
 
+    warning("The introduction does not contain a package yet")?;
     outro("Created introduction template. Use 'borderless merge' to merge it with a package definition.")?;
 
     Ok(())
 }
 
+fn select_participants(
+    node: &Node,
+    filter: Option<&str>,
+    use_cache: bool,
+) -> Result<Vec<BorderlessId>> {
+    let peers = node.network_peers_cached(filter, use_cache)?;
+    match filter {
+        Some(filter) if peers.is_empty() => {
+            bail!(CliError::usage(format!(
+                "no peers match the filter '{filter}'"
+            )))
+        }
+        None if peers.is_empty() => bail!("node '{}' has no peers", node.link().name),
+        _ => (),
+    }
+
+    ensure_interactive("pass --participants-file to select the participants")?;
+    let mut participants = multiselect("Select peers for contract");
+
+    for (name, id) in peers {
+        participants = participants.item(id, format!("{} - {}", name, id), "");
+    }
+    Ok(participants.filter_mode().interact()?)
+}
+
+/// Reads the participants from a file and resolves names against the peers of the node
+fn read_participants(node: &Node, path: &Path, use_cache: bool) -> Result<Vec<BorderlessId>> {
+    ensure_file(path, "participants file")?;
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read '{}'", path.display()))?;
+
+    // The peers are only fetched, if we actually have to resolve a name
+    let mut peers = None;
+    let mut participants = Vec::new();
+    for entry in content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+    {
+        if let Ok(id) = entry.parse::<BorderlessId>() {
+            participants.push(id);
+            continue;
+        }
+        if peers.is_none() {
+            peers = Some(node.network_peers_cached(None, use_cache)?);
+        }
+        let id = peers
+            .iter()
+            .flatten()
+            .find(|(name, _)| name == entry)
+            .map(|(_, id)| *id)
+            .ok_or_else(|| {
+                CliError::usage(format!(
+                    "unknown participant '{entry}' in '{}' - neither an id nor the name of a peer",
+                    path.display()
+                ))
+            })?;
+        participants.push(id);
+    }
+    info(format!(
+        "Read {} participants from '{}'",
+        participants.len(),
+        path.display()
+    ))?;
+    Ok(participants)
+}
+
 fn empty_obj() -> Value {
     Value::Object(serde_json::Map::default())
 }
@@ -0,0 +1,115 @@
+use std::fmt;
+
+/// Category of a failure
+///
+/// Every category maps to a distinct exit code, so scripts and CI-pipelines
+/// can tell apart *why* a command failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Generic failure
+    Failure,
+    /// Invalid usage of the cli ( bad arguments, missing files etc. )
+    Usage,
+    /// Compilation of the project failed
+    Build,
+    /// Communication with a node or registry failed
+    Network,
+    /// The user aborted the command with Ctrl-C
+    Interrupted,
+}
+
+impl ErrorKind {
+    /// Returns the exit code that corresponds to this error category
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorKind::Failure => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Build => 3,
+            ErrorKind::Network => 4,
+            // Conventional code of shells for a process, that was terminated by SIGINT
+            ErrorKind::Interrupted => 130,
+        }
+    }
+
+    /// Determines the category of some error
+    ///
+    /// Walks the chain of causes and returns the first explicitly tagged category.
+    /// Errors from the http-client are always categorized as [`ErrorKind::Network`].
+    pub fn of(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<CliError>() {
+                return e.kind;
+            }
+            if cause.is::<reqwest::Error>() {
+                return ErrorKind::Network;
+            }
+        }
+        ErrorKind::Failure
+    }
+}
+
+/// An error that is tagged with an [`ErrorKind`]
+#[derive(Debug)]
+pub struct CliError {
+    kind: ErrorKind,
+    msg: String,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, msg: impl fmt::Display) -> Self {
+        Self {
+            kind,
+            msg: msg.to_string(),
+        }
+    }
+
+    /// Shorthand for [`ErrorKind::Usage`] errors
+    pub fn usage(msg: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Usage, msg)
+    }
+
+    /// Shorthand for [`ErrorKind::Build`] errors
+    pub fn build(msg: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Build, msg)
+    }
+
+    /// Shorthand for [`ErrorKind::Network`] errors
+    pub fn network(msg: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Network, msg)
+    }
+
+    /// Shorthand for [`ErrorKind::Interrupted`] errors
+    pub fn interrupted(msg: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Interrupted, msg)
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn untagged_errors_are_failures() {
+        let err = anyhow!("something went wrong");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Failure);
+        assert_eq!(ErrorKind::of(&err).exit_code(), 1);
+    }
+
+    #[test]
+    fn tagged_errors_survive_context() {
+        let err: anyhow::Error = Err::<(), _>(CliError::build("WASM build failed"))
+            .context("failed to create package")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Build);
+        assert_eq!(ErrorKind::of(&err).exit_code(), 3);
+    }
+}
@@ -0,0 +1,105 @@
+//! Handling of Ctrl-C during long running operations
+//!
+//! By default, an interrupt terminates the cli immediately. That would leave a running cargo process,
+//! a half-drawn spinner or a temporary file behind. While an [`InterruptGuard`] is alive, an interrupt is
+//! only recorded instead, so the operation can stop its child processes and clean up, before it fails with
+//! [`ErrorKind::Interrupted`]( crate::error::ErrorKind::Interrupted ).
+//!
+//! Interrupts are only caught on unix-platforms; everywhere else they keep terminating the cli.
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Interval, in which [`ChildWatcher`] checks for an interrupt
+const WATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Returns true, if an interrupt was caught
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Catches interrupts until the guard is dropped
+pub struct InterruptGuard {
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+/// Starts catching interrupts ( see [`InterruptGuard`] )
+pub fn catch() -> InterruptGuard {
+    #[cfg(unix)]
+    {
+        extern "C" fn on_interrupt(_: libc::c_int) {
+            // Storing into an atomic is async-signal-safe
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+        let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only stores into an atomic
+        let previous = unsafe { libc::signal(libc::SIGINT, handler) };
+        InterruptGuard { previous }
+    }
+    #[cfg(not(unix))]
+    InterruptGuard {}
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: restores the handler, that was installed before
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+    }
+}
+
+/// Forwards a caught interrupt to a child process
+///
+/// Ctrl-C in a terminal reaches the child anyway, since it belongs to the same process group.
+/// The watcher makes sure the child also stops, if only the cli itself was interrupted ( e.g. via `kill -INT` ).
+pub struct ChildWatcher {
+    done: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ChildWatcher {
+    /// Starts watching; must be stopped before the child is reaped, so its pid cannot be reused
+    pub fn start(child_pid: u32) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let done = done.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    if interrupted() {
+                        #[cfg(unix)]
+                        // SAFETY: the child has not been reaped yet, so the pid still belongs to it
+                        unsafe {
+                            libc::kill(child_pid as libc::pid_t, libc::SIGINT);
+                        }
+                        #[cfg(not(unix))]
+                        let _ = child_pid;
+                        return;
+                    }
+                    thread::sleep(WATCH_INTERVAL);
+                }
+            })
+        };
+        ChildWatcher {
+            done,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stops watching and waits for the watcher thread to finish
+    pub fn stop(mut self) {
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
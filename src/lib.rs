@@ -0,0 +1,12 @@
+//! Building blocks of the borderless cli
+//!
+//! The modules of this library never print anything or prompt the user;
+//! they only return data or errors. The interactive commands of the `borderless` binary are built on top of them,
+//! so other tools can reuse the same logic ( e.g. to pack or validate packages in their own pipelines ).
+pub mod build;
+pub mod error;
+pub mod manifest;
+pub mod package;
+pub mod schema;
+pub mod template;
+pub mod wasm;
@@ -0,0 +1,107 @@
+//! Logging facade
+//!
+//! Thin wrapper around [`cliclack::log`], that additionally writes every message
+//! into a log-file, if one was registered via [`init_log_file`].
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static LOG_FILE: OnceCell<Mutex<File>> = OnceCell::new();
+
+/// Registers a log-file, that receives a timestamped copy of all messages
+///
+/// The file is opened in append mode, so consecutive runs end up in the same file.
+pub fn init_log_file(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open log-file '{}'", path.display()))?;
+    LOG_FILE.set(Mutex::new(file)).expect("log-file is unset");
+    record("START", std::env::args().collect::<Vec<_>>().join(" "));
+    Ok(())
+}
+
+/// Writes a message only to the log-file (if any)
+///
+/// Use this for output that should not be printed to the terminal, like the raw output of cargo.
+pub fn record(level: &str, text: impl Display) {
+    let Some(file) = LOG_FILE.get() else {
+        return;
+    };
+    let Ok(mut file) = file.lock() else {
+        return;
+    };
+    let ts = timestamp();
+    for line in text.to_string().lines() {
+        // Failing to write the log should never abort a command
+        let _ = writeln!(file, "{ts} {level:<7} {line}");
+    }
+}
+
+pub fn info(text: impl Display) -> io::Result<()> {
+    record("INFO", &text);
+    cliclack::log::info(text)
+}
+
+pub fn warning(message: impl Display) -> io::Result<()> {
+    record("WARNING", &message);
+    cliclack::log::warning(message)
+}
+
+pub fn error(message: impl Display) -> io::Result<()> {
+    record("ERROR", &message);
+    cliclack::log::error(message)
+}
+
+pub fn success(message: impl Display) -> io::Result<()> {
+    record("SUCCESS", &message);
+    cliclack::log::success(message)
+}
+
+/// Returns the current UTC time formatted as RFC 3339 timestamp (with milliseconds)
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format_utc(now.as_secs(), now.subsec_millis())
+}
+
+fn format_utc(secs: u64, millis: u32) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (hour, min, sec) = (rem / 3600, (rem % 3600) / 60, rem % 60);
+
+    // Convert days since epoch into a civil date
+    // ( see http://howardhinnant.github.io/date_algorithms.html#civil_from_days )
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{min:02}:{sec:02}.{millis:03}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_timestamps() {
+        assert_eq!(format_utc(0, 0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_utc(951_782_400, 5), "2000-02-29T00:00:00.005Z");
+        assert_eq!(format_utc(1_750_598_445, 123), "2025-06-22T13:20:45.123Z");
+    }
+}
@@ -1,11 +1,16 @@
 // use crate::packager::pack_wasm_contract;
-use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand};
-use cliclack::log::error;
-use std::{fs, path::PathBuf};
+use anyhow::Result;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use std::{path::PathBuf, process::ExitCode};
 
 // pub mod packager;
-mod template;
+use borderless_cli::{build, error, manifest, package, schema, template, wasm};
+
+mod log;
+
+mod interrupt;
+
+mod util;
 
 mod cli;
 
@@ -19,6 +24,26 @@ pub struct Cli {
     #[arg(long)]
     private_key: Option<String>,
 
+    /// Write a timestamped log of all messages to the given file
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Use a separate set of links and keys ( defaults to the "default" profile )
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Automatically confirm prompts ( like overwriting existing files )
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Override the version of the node API ( e.g. "v1" )
+    #[arg(long)]
+    api_version: Option<String>,
+
+    /// Print method, url, headers and body size of every request against a node
+    #[arg(long)]
+    dump_request: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -52,85 +77,547 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Initializes a new project
-    Init { project_name: Option<String> },
+    Init(InitArgs),
 
     /// Creates a new package from an existing project
-    Pack { project_path: PathBuf },
+    Pack(PackArgs),
 
     /// Merges an introduction with a package.json
-    Merge {
-        introduction: PathBuf,
-        package_json: PathBuf,
-    },
+    Merge(MergeArgs),
+
+    /// Converts a package file into another encoding
+    Convert(ConvertArgs),
 
     /// Deploys a package to a node
-    Deploy { path: PathBuf },
+    Deploy(DeployArgs),
 
     /// Links the cli to a node or registry
     ///
     /// This makes the node or registry available for commands like `publish` or `deploy`
-    Link,
+    Link(LinkArgs),
+
+    /// Lists the contracts and agents, that are deployed on a node
+    Status,
 
     /// Publishes a package to some registry
     Publish,
 
+    /// Queries a linked registry
+    #[command(subcommand)]
+    Registry(RegistryCmd),
+
+    /// Downloads a package from a registry
+    Pull(PullArgs),
+
     /// Create a new template
     #[command(subcommand)]
     Template(TemplateCmd),
+
+    /// Manage the configuration of the cli
+    #[command(subcommand)]
+    Config(ConfigCmd),
+
+    /// Shows the environment of the cli ( versions, paths, toolchain )
+    Info(InfoArgs),
+
+    /// Work with the Manifest.toml of a project
+    #[command(subcommand)]
+    Manifest(ManifestCmd),
+}
+
+#[derive(Args)]
+pub struct InitArgs {
+    pub project_name: Option<String>,
+
+    /// Type of the package; skips the selection prompt
+    #[arg(long = "type", value_enum)]
+    pub pkg_type: Option<PackageType>,
+
+    /// Name of the template to use; skips the selection prompt
+    #[arg(long, conflicts_with = "pkg_type")]
+    pub template: Option<String>,
+
+    /// Fail, if the selected template does not have this version ( see `--list-templates` )
+    #[arg(long)]
+    pub template_version: Option<String>,
+
+    /// List the available templates and their versions
+    #[arg(long, exclusive = true)]
+    pub list_templates: bool,
+
+    /// Capabilities of the agent ( comma separated ); skips the selection prompt
+    ///
+    /// Pass `--capabilities none` to grant no capabilities at all.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub capabilities: Option<Vec<Capability>>,
+
+    /// Add an example test module to the generated lib.rs
+    #[arg(long)]
+    pub with_tests: bool,
+
+    /// Scaffold into an existing directory; files that already exist are skipped
+    #[arg(long)]
+    pub force: bool,
+
+    /// Author of the package, either `Name <email>` or just `Name`; overrides the `author` of your config
+    #[arg(long)]
+    pub author: Option<borderless_pkg::Author>,
+}
+
+/// Package types, that can be selected on the commandline
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PackageType {
+    Contract,
+    Agent,
+}
+
+impl From<PackageType> for borderless_pkg::PkgType {
+    fn from(value: PackageType) -> Self {
+        match value {
+            PackageType::Contract => borderless_pkg::PkgType::Contract,
+            PackageType::Agent => borderless_pkg::PkgType::Agent,
+        }
+    }
+}
+
+/// Capability, that can be granted to an agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Capability {
+    /// Make http-calls
+    Network,
+    /// Establish websocket connections
+    Websocket,
+    /// No capabilities at all
+    None,
+}
+
+#[derive(Args)]
+pub struct PackArgs {
+    /// Path to the project ( defaults to the current directory )
+    #[arg(default_value = ".")]
+    pub project_path: PathBuf,
+
+    /// Path to the Manifest.toml or Cargo.toml of the project ( the project root is its directory )
+    #[arg(long, conflicts_with = "project_path")]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Print the JSON schema of the package.json and exit
+    #[arg(long)]
+    pub json_schema: bool,
+
+    /// Validate that the project can be packed, without writing the package.json
+    #[arg(long)]
+    pub check_only: bool,
+
+    /// Also write a package.lock with provenance information about the build
+    #[arg(long, conflicts_with = "check_only")]
+    pub lock: bool,
+
+    /// Attach a human readable release note to the package.lock ( e.g. "fixes rounding bug" )
+    #[arg(long, requires = "lock")]
+    pub message: Option<String>,
+
+    /// Record the digest of the Manifest.toml in the package.lock, so later changes of the manifest can be detected
+    #[arg(long, requires = "lock")]
+    pub emit_manifest_digest: bool,
+
+    /// Forward `--locked` to cargo, failing if the Cargo.lock needs to be updated
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Forward `--frozen` to cargo, which implies `--locked` and `--offline`
+    #[arg(long)]
+    pub frozen: bool,
+
+    /// Fail if the build produced any compiler warnings
+    #[arg(long)]
+    pub deny_warnings: bool,
+
+    /// Show the crates, that took the longest until cargo reported their artifact
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Cargo features to enable ( comma separated ); overrides the `[build] features` of the manifest
+    ///
+    /// Pass an empty list ( `--features ""` ) to build without the features of the manifest.
+    #[arg(long, value_delimiter = ',')]
+    pub features: Option<Vec<String>>,
+
+    /// Target triple to compile the project for ( e.g. "wasm32-wasip1" )
+    #[arg(long, value_name = "TRIPLE")]
+    pub wasm_target: Option<String>,
+
+    /// Run cargo with `-v` ( or `-vv` if given twice ) and stream its full output
+    #[arg(long, action = ArgAction::Count)]
+    pub cargo_verbose: u8,
+
+    /// Write the package to stdout instead of a file ( all other output goes to stderr )
+    #[arg(long, conflicts_with_all = ["check_only", "verify_reproducible"])]
+    pub stdout: bool,
+
+    /// Encoding of the package file
+    #[arg(long, value_enum, default_value_t)]
+    pub format: package::PkgFormat,
+
+    /// Write the json package without any whitespace, instead of pretty-printing it
+    #[arg(long)]
+    pub minify: bool,
+
+    /// Remove debug- and toolchain-related custom sections from the wasm binary
+    #[arg(long)]
+    pub strip: bool,
+
+    /// Skip the build, if no sources changed since the given git reference ( e.g. the last release tag )
+    ///
+    /// Only `src/`, the Cargo.toml and the Manifest.toml of the project are considered.
+    #[arg(long, value_name = "GIT_REF")]
+    pub since: Option<String>,
+
+    /// Build the project twice in fresh target directories and compare the binaries, without writing a package
+    #[arg(long, conflicts_with_all = ["check_only", "lock"])]
+    pub verify_reproducible: bool,
+
+    /// Keep the temporary target directories of `--verify-reproducible` and print their paths ( for debugging )
+    #[arg(long, requires = "verify_reproducible")]
+    pub keep_temp: bool,
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Package file in any of the supported encodings
+    pub input: PathBuf,
+
+    /// Target encoding
+    #[arg(long, value_enum)]
+    pub to: package::PkgFormat,
+
+    /// Output file ( defaults to the default file name of the target encoding, next to the input )
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    pub introduction: PathBuf,
+
+    /// Package file in any of the supported encodings ( `-` reads it from stdin )
+    pub package_json: PathBuf,
+
+    /// Print the merged introduction to stdout instead of overwriting the introduction
+    #[arg(long, visible_alias = "stdout")]
+    pub dry_run: bool,
+
+    /// Check that the package is intact ( matching digest, valid wasm binary ) before merging
+    #[arg(long)]
+    pub validate_package: bool,
+
+    /// Replace the `initial_state` of the introduction with the contents of this json file
+    #[arg(long)]
+    pub state: Option<PathBuf>,
+
+    /// Validate the merged introduction against this JSON Schema before writing it
+    #[arg(long, value_name = "PATH")]
+    pub schema: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct LinkArgs {
+    #[command(subcommand)]
+    pub command: Option<LinkCmd>,
+
+    /// Read the API-key from a file instead of prompting for it
+    #[arg(long, conflicts_with = "api_key_stdin")]
+    pub api_key_file: Option<PathBuf>,
+
+    /// Read the API-key from stdin instead of prompting for it
+    #[arg(long)]
+    pub api_key_stdin: bool,
+}
+
+#[derive(Subcommand)]
+pub enum LinkCmd {
+    /// Checks, that all linked nodes are reachable
+    TestAll(TestAllArgs),
+
+    /// Shows all details of a single link
+    Show(ShowLinkArgs),
+
+    /// Imports links from a file ( in the format of the link-db )
+    Import(ImportLinksArgs),
+
+    /// Asks the node of a link for the version of its API again
+    Refresh(RefreshLinkArgs),
+}
+
+#[derive(Args)]
+pub struct RefreshLinkArgs {
+    /// Name of the link
+    pub name: String,
+}
+
+#[derive(Args)]
+pub struct ImportLinksArgs {
+    /// File with one json encoded link per line
+    pub file: PathBuf,
+
+    /// How to handle links, whose name already exists; prompts for every conflict if not set
+    #[arg(long, value_enum)]
+    pub on_conflict: Option<api::ConflictStrategy>,
+}
+
+#[derive(Args)]
+pub struct ShowLinkArgs {
+    /// Name of the link
+    pub name: String,
+
+    /// Print the API-key instead of masking it
+    #[arg(long)]
+    pub show_secret: bool,
+
+    /// Format of the output ( json is printed to stdout )
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct TestAllArgs {
+    /// Timeout in seconds for every node
+    #[arg(long, default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Fail, if any node is unreachable
+    #[arg(long)]
+    pub require_all: bool,
+
+    /// Format of the result ( json is printed to stdout )
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct DeployArgs {
+    /// Introduction, that contains the package ( `-` reads it from stdin )
+    #[arg(required_unless_present = "from_package")]
+    pub path: Option<PathBuf>,
+
+    /// Package to deploy ( `-` reads it from stdin ); it is merged in memory into the introduction given by `--introduction`
+    #[arg(long, requires = "introduction", conflicts_with = "path")]
+    pub from_package: Option<PathBuf>,
+
+    /// Introduction template for `--from-package`
+    #[arg(long, requires = "from_package")]
+    pub introduction: Option<PathBuf>,
+
+    /// Replace an existing contract or agent with the same id ( e.g. to ship a new version )
+    #[arg(long)]
+    pub upgrade: bool,
+
+    /// Show the node-info and the number of peers of the node, and confirm the deployment ( even on development nodes )
+    #[arg(long)]
+    pub preflight: bool,
+
+    /// Validate the introduction against this JSON Schema before deploying it
+    #[arg(long, value_name = "PATH")]
+    pub schema: Option<PathBuf>,
+
+    /// Replace the participants of the introduction with these ids ( comma separated )
+    #[arg(long, value_name = "IDS", value_delimiter = ',')]
+    pub participants: Option<Vec<String>>,
+
+    /// Abort, unless the digest of the package matches this ( hex encoded ) digest
+    #[arg(long, value_name = "HEX")]
+    pub expected_digest: Option<String>,
+
+    /// Wait until the node reports the contract or agent as active
+    #[arg(long)]
+    pub wait: bool,
+
+    /// Maximum time in seconds to wait for the contract or agent to become active
+    #[arg(long, default_value_t = 60, requires = "wait")]
+    pub wait_timeout: u64,
+
+    /// Only print the id of the deployed contract or agent ( to stdout )
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Format of the result ( json is printed to stdout )
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// Format of the output
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Shorthand for `--format json`
+    #[arg(long, conflicts_with = "format")]
+    pub json: bool,
+}
+
+/// Format of the result of a command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable output
+    #[default]
+    Text,
+    /// Machine readable json
+    Json,
 }
 
 #[derive(Subcommand)]
 pub enum TemplateCmd {
-    Introduction,
+    /// Creates an introduction template with participants from a linked node
+    Introduction(IntroductionArgs),
 }
 
-fn main() -> Result<()> {
-    // Register config object
-    config::init_config()?;
+#[derive(Args)]
+pub struct IntroductionArgs {
+    /// Only offer peers whose name or id contains this string ( case-insensitive )
+    #[arg(long)]
+    pub filter: Option<String>,
+
+    /// Read the participants from a file instead of selecting them
+    ///
+    /// The file contains one participant per line, either as id or as name of a peer.
+    /// Empty lines and lines starting with '#' are ignored.
+    #[arg(long, conflicts_with = "filter")]
+    pub participants_file: Option<PathBuf>,
 
-    // Check that data directory exists
-    let data_dir = config::get_config()
-        .data_dir()
-        .context("failed to get data directory - consider setting it manually in your config")?;
+    /// Path of the generated introduction; parent directories are created as needed
+    #[arg(short, long, default_value = "introduction.json")]
+    pub output: PathBuf,
 
-    if !data_dir.exists() {
-        fs::create_dir_all(&data_dir)?;
-    }
+    /// Reuse the peers of a previous request to the same node ( if they are younger than an hour )
+    #[arg(long)]
+    pub use_cached_peers: bool,
+
+    /// Always fetch the peers from the node ( overrides `--use-cached-peers` )
+    #[arg(long, overrides_with = "use_cached_peers")]
+    pub refresh_peers: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCmd {
+    /// Writes the default config to disk
+    Init {
+        /// Overwrite an existing config ( a backup is saved to `config.toml.bak` )
+        #[arg(long)]
+        force: bool,
+    },
 
-    if !data_dir.is_dir() {
-        bail!("data-directory {} is not a directory!", data_dir.display());
+    /// Checks the config file and reports all problems
+    Validate,
+}
+
+#[derive(Subcommand)]
+pub enum RegistryCmd {
+    /// Lists the packages, that are published in the registry
+    ListPackages(ListPackagesArgs),
+}
+
+#[derive(Args)]
+pub struct ListPackagesArgs {
+    /// Format of the output ( json is printed to stdout )
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct PullArgs {
+    /// Package as `<name>@<version>` ( without a version, the latest one is downloaded )
+    pub package: String,
+
+    /// Output file ( defaults to `package.json` or `package.postcard` in the current directory )
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Encoding of the downloaded package
+    #[arg(long, value_enum, default_value_t)]
+    pub format: package::PkgFormat,
+
+    /// Abort, unless the digest of the package matches this ( hex encoded ) digest
+    #[arg(long, value_name = "HEX")]
+    pub expected_digest: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum ManifestCmd {
+    /// Reconstructs a Manifest.toml from a package
+    FromPackage(FromPackageArgs),
+}
+
+#[derive(Args)]
+pub struct FromPackageArgs {
+    /// Package file ( json or postcard )
+    pub package: PathBuf,
+
+    /// Write the manifest to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let _ = log::error(format!("{e:#}"));
+            ExitCode::from(error::ErrorKind::of(&e).exit_code())
+        }
     }
+}
 
+fn run() -> Result<()> {
     // Parse arguments
     let cli = Cli::parse();
-    let result = match cli.command {
-        Commands::Init { project_name } => cli::handle_init(project_name),
-        Commands::Pack { project_path } => cli::handle_pack(project_path),
-        Commands::Merge {
-            introduction,
-            package_json,
-        } => cli::handle_merge(introduction, package_json),
-        Commands::Deploy { path } => cli::handle_deploy(path),
-        Commands::Link => cli::handle_link(),
-        Commands::Publish => todo!(),
-        Commands::Template(template) => cli::handle_template(template),
-    };
 
-    if let Err(e) = result {
-        error(format!("{e}"))?;
+    if let Some(log_file) = &cli.log_file {
+        log::init_log_file(log_file)?;
     }
 
-    Ok(())
+    // Register config object ( `config validate` reports a broken config instead of failing on it )
+    if !matches!(cli.command, Commands::Config(ConfigCmd::Validate)) {
+        config::init_config(cli.profile)?;
+    }
+
+    api::init_api_options(api::ApiOptions {
+        api_version: cli.api_version,
+        dump_requests: cli.dump_request,
+    })?;
+
+    // NOTE: The data directory is created by the commands, that persist something ( see `config::create_profile_dir` )
+    match cli.command {
+        Commands::Init(args) => cli::handle_init(args, cli.yes),
+        Commands::Pack(args) => cli::handle_pack(args, cli.yes),
+        Commands::Merge(args) => cli::handle_merge(args, cli.yes),
+        Commands::Convert(args) => cli::handle_convert(args, cli.yes),
+        Commands::Deploy(args) => cli::handle_deploy(args, cli.yes),
+        Commands::Link(args) => cli::handle_link(args),
+        Commands::Status => cli::handle_status(),
+        Commands::Publish => todo!(),
+        Commands::Registry(cmd) => cli::handle_registry(cmd),
+        Commands::Pull(args) => cli::handle_pull(args, cli.yes),
+        Commands::Template(template) => cli::handle_template(template, cli.yes),
+        Commands::Config(cmd) => cli::handle_config(cmd, cli.yes),
+        Commands::Manifest(cmd) => cli::handle_manifest(cmd, cli.yes),
+        Commands::Info(args) => cli::handle_info(args),
+    }
 }
 
 mod config {
-    use anyhow::{Context, Result};
-    use borderless_pkg::Author;
+    use anyhow::{bail, Context, Result};
+    use borderless_pkg::{Author, PkgType};
+    use cliclack::confirm;
     use once_cell::sync::OnceCell;
     use serde::{Deserialize, Serialize};
     use std::env;
-    use std::fs::read_to_string;
-    use std::path::PathBuf;
+    use std::fs::{self, read_to_string};
+    use std::path::{Path, PathBuf};
+    use url::Url;
+
+    use crate::{api::LINK_DB_FILE, error::CliError, log::info, util::is_interactive};
 
     /// Name of the config file
     const CONFIG_FILE_NAME: &str = "config.toml";
@@ -138,10 +625,16 @@ mod config {
     /// Name of the specific config directory for our config
     const CONFIG_DIR_NAME: &str = "borderless-cli";
 
+    /// Name of the profile, that is used if no profile is specified
+    pub const DEFAULT_PROFILE: &str = "default";
+
+    /// Files in the data directory, that belong to a profile
+    const PROFILE_FILES: &[&str] = &[LINK_DB_FILE];
+
     pub static CONFIG: OnceCell<Config> = OnceCell::new();
 
     /// Configuration of the cmdline interface
-    #[derive(Debug, Default, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
     pub struct Config {
         /// Author information
@@ -150,37 +643,215 @@ mod config {
         /// If true, the user has to confirm the creation of new directories
         pub confirm_creation: bool,
 
+        /// Package type, that is preselected when initializing a new package
+        ///
+        /// Falls back to `contract`.
+        pub default_package_type: Option<PkgType>,
+
         /// Base data directory.
         ///
-        /// Defaults to `XDG_DATA_HOME`
+        /// Defaults to `XDG_DATA_HOME`. A leading `~` is expanded to the home directory and
+        /// relative paths are resolved against the directory of the config file ( not the current directory ).
         data_directory: Option<PathBuf>,
+
+        /// Proxy for all requests against nodes and registries
+        ///
+        /// Takes precedence over the `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` environment variables.
+        /// Hosts listed in `NO_PROXY` always bypass the proxy.
+        pub proxy: Option<Url>,
+
+        /// Names of links, that point to development nodes
+        ///
+        /// Deployments to all other nodes have to be confirmed ( unless `--yes` is given ).
+        #[serde(default)]
+        pub dev_nodes: Vec<String>,
+
+        /// Selected profile ( set via commandline )
+        #[serde(skip)]
+        profile: Option<String>,
+
+        /// Directory of the config file, that was loaded
+        #[serde(skip)]
+        config_dir: Option<PathBuf>,
     }
 
     impl Config {
         pub fn data_dir(&self) -> Result<PathBuf> {
             match &self.data_directory {
-                Some(dir) => Ok(dir.clone()),
+                Some(dir) => Ok(resolve_path(dir, self.config_dir.as_deref())),
                 None => {
-                    let base_dir: PathBuf = env::var("XDG_DATA_HOME")?.into();
+                    let base_dir = base_data_dir().context(
+                        "failed to determine the data directory - consider to set XDG_DATA_HOME or `data-directory` in the config.",
+                    )?;
                     Ok(base_dir.join("borderless-cli"))
                 }
             }
         }
+
+        /// Returns the name of the selected profile
+        pub fn profile(&self) -> &str {
+            self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+        }
+
+        /// Returns the data directory of the selected profile
+        ///
+        /// Links, keys and caches are stored per profile under `<data-dir>/profiles/<name>`.
+        pub fn profile_dir(&self) -> Result<PathBuf> {
+            Ok(self.data_dir()?.join("profiles").join(self.profile()))
+        }
     }
 
-    fn get_config_file_dir() -> Option<PathBuf> {
-        // use XGD_CONFIG_HOME if possible, otherwise $HOME/.config
-        let base_dir = if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME") {
-            PathBuf::from(xdg_config_home)
+    /// Expands a leading `~` and resolves relative paths against `base` ( if given )
+    fn resolve_path(path: &Path, base: Option<&Path>) -> PathBuf {
+        if let Ok(rest) = path.strip_prefix("~") {
+            if let Some(home) = env_path("HOME").or_else(|| env_path("USERPROFILE")) {
+                return home.join(rest);
+            }
+        }
+        match base {
+            Some(base) if path.is_relative() => base.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Returns the directory of the selected profile and creates it, if it does not exist yet
+    ///
+    /// Only commands that persist state should call this, so all other commands run without touching the filesystem.
+    pub fn create_profile_dir() -> Result<PathBuf> {
+        let config = get_config();
+        let data_dir = config.data_dir().context(
+            "failed to get data directory - consider setting it manually in your config",
+        )?;
+        if !data_dir.exists() {
+            fs::create_dir_all(&data_dir)?;
+        }
+        if !data_dir.is_dir() {
+            bail!("data-directory {} is not a directory!", data_dir.display());
+        }
+
+        let profile_dir = config.profile_dir()?;
+        if !profile_dir.exists() {
+            fs::create_dir_all(&profile_dir)?;
+            if config.profile() == DEFAULT_PROFILE {
+                migrate_legacy_data(&data_dir, &profile_dir)?;
+            }
+        }
+        Ok(profile_dir)
+    }
+
+    /// Returns the path of a file in the selected profile, without creating or migrating anything
+    ///
+    /// Until [`create_profile_dir`] migrated it, the default profile still uses the legacy data.
+    pub fn profile_file(name: &str) -> Result<PathBuf> {
+        let config = get_config();
+        Ok(locate_profile_file(
+            &config.data_dir()?,
+            &config.profile_dir()?,
+            config.profile() == DEFAULT_PROFILE,
+            name,
+        ))
+    }
+
+    fn locate_profile_file(
+        data_dir: &Path,
+        profile_dir: &Path,
+        is_default: bool,
+        name: &str,
+    ) -> PathBuf {
+        let legacy = data_dir.join(name);
+        if is_default && !profile_dir.exists() && legacy.is_file() {
+            legacy
         } else {
-            let home = env::var("HOME").ok()?;
-            PathBuf::from(home).join(".config")
-        };
+            profile_dir.join(name)
+        }
+    }
+
+    /// Moves data, that was created before profiles existed, into the given profile directory
+    fn migrate_legacy_data(data_dir: &Path, profile_dir: &Path) -> Result<()> {
+        for name in PROFILE_FILES {
+            let legacy = data_dir.join(name);
+            let target = profile_dir.join(name);
+            if legacy.is_file() && !target.exists() {
+                fs::rename(&legacy, &target).with_context(|| {
+                    format!("failed to move '{}' into profile", legacy.display())
+                })?;
+                info(format!(
+                    "Moved '{}' to '{}'",
+                    legacy.display(),
+                    target.display()
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_profile(name: &str) -> Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(CliError::usage(format!(
+                "invalid profile name '{name}' - only letters, numbers, hyphens and underscores allowed"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Returns the value of an environment variable, if it is set to a non-empty path
+    fn env_path(var: &str) -> Option<PathBuf> {
+        env::var_os(var)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Platform specific base directory for config files
+    ///
+    /// `XDG_CONFIG_HOME` is honored on every platform. Otherwise this is
+    /// `$HOME/.config` on linux, `$HOME/Library/Application Support` on macOS and `%APPDATA%` on windows.
+    fn base_config_dir() -> Option<PathBuf> {
+        if let Some(dir) = env_path("XDG_CONFIG_HOME") {
+            return Some(dir);
+        }
+        if cfg!(windows) {
+            env_path("APPDATA")
+        } else if cfg!(target_os = "macos") {
+            env_path("HOME").map(|home| home.join("Library/Application Support"))
+        } else {
+            env_path("HOME").map(|home| home.join(".config"))
+        }
+    }
+
+    /// Platform specific base directory for persistent data
+    ///
+    /// `XDG_DATA_HOME` is honored on every platform. Otherwise this is
+    /// `$HOME/.local/share` on linux, `$HOME/Library/Application Support` on macOS and `%LOCALAPPDATA%` on windows.
+    fn base_data_dir() -> Option<PathBuf> {
+        if let Some(dir) = env_path("XDG_DATA_HOME") {
+            return Some(dir);
+        }
+        if cfg!(windows) {
+            env_path("LOCALAPPDATA")
+        } else if cfg!(target_os = "macos") {
+            env_path("HOME").map(|home| home.join("Library/Application Support"))
+        } else {
+            env_path("HOME").map(|home| home.join(".local/share"))
+        }
+    }
+
+    fn get_config_file_dir() -> Option<PathBuf> {
+        let base_dir = base_config_dir()?;
 
         if !base_dir.exists() {
             return None;
         }
 
+        // System wide configs only exist on linux ( and other xdg-platforms )
+        if cfg!(windows) || cfg!(target_os = "macos") {
+            return Some(base_dir);
+        }
+
         // check XDG_CONFIG_DIRS for system wide configs
         let config_dirs = env::var("XDG_CONFIG_DIRS").unwrap_or_else(|_| "/etc/xdg".to_string());
 
@@ -212,24 +883,16 @@ mod config {
     /// Initializes the config
     ///
     /// This registers the static, global variable `CONFIG`, which can be easily accessed via [`get_config()`]
-    pub fn init_config() -> Result<()> {
-        let config = match config_file() {
-            Some(file) => {
-                // Read config from disk
-                let content = read_to_string(file)?;
-                toml::from_str(&content)?
-            }
+    pub fn init_config(profile: Option<String>) -> Result<()> {
+        if let Some(name) = &profile {
+            validate_profile(name)?;
+        }
+
+        let mut config: Config = match config_file() {
+            Some(file) => read_config(&file)?,
             None => {
                 let default_config = Config::default();
-                let config_file_path = get_config_file_path()
-                    .context("failed to get config directory - consider to set XDG_CONFIG_HOME.")?;
-
-                if let Some(parent) = config_file_path.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
-
-                let config_content = toml::to_string_pretty(&default_config)?;
-                std::fs::write(&config_file_path, config_content)?;
+                let config_file_path = write_config(&default_config, false)?;
 
                 println!("Initial setup complete! We've created a configuration file at:");
                 println!("  {}", config_file_path.display());
@@ -239,16 +902,192 @@ mod config {
             }
         };
 
+        config.profile = profile;
         CONFIG.set(config).expect("config is unset");
         Ok(())
     }
 
+    /// Reads the config from disk
+    ///
+    /// If the config cannot be parsed, the user is offered to back it up and to start over with the default config.
+    fn read_config(file: &Path) -> Result<Config> {
+        let content = read_to_string(file)
+            .with_context(|| format!("failed to read config '{}'", file.display()))?;
+        let err = match toml::from_str::<Config>(&content) {
+            Ok(mut config) => {
+                config.config_dir = file.parent().map(Path::to_path_buf);
+                return Ok(config);
+            }
+            Err(e) => e,
+        };
+        let msg = describe_parse_error(file, &content, &err);
+        // Without a terminal we cannot ask, so the broken config is left untouched
+        if is_interactive()
+            && confirm(format!(
+                "{msg}\nBack up the broken config and continue with the default config?"
+            ))
+            .initial_value(false)
+            .interact()?
+        {
+            let path = write_config(&Config::default(), true)?;
+            info(format!("Wrote default config to '{}'", path.display()))?;
+            return Ok(Config {
+                config_dir: path.parent().map(Path::to_path_buf),
+                ..Default::default()
+            });
+        }
+        bail!(CliError::usage(msg))
+    }
+
+    /// Checks a config file and returns all problems, that were found
+    ///
+    /// The file is parsed like in [`init_config`], but instead of stopping at the first problem,
+    /// unknown fields and values that only fail when they are used are reported as well.
+    pub fn validate_config(file: &Path) -> Result<Vec<String>> {
+        let content = read_to_string(file)
+            .with_context(|| format!("failed to read config '{}'", file.display()))?;
+        let mut config = match toml::from_str::<Config>(&content) {
+            Ok(config) => config,
+            // Without a parsed config, there is nothing else we could check
+            Err(e) => return Ok(vec![describe_parse_error(file, &content, &e)]),
+        };
+        config.config_dir = file.parent().map(Path::to_path_buf);
+
+        let mut problems = Vec::new();
+        let table: toml::Table = toml::from_str(&content)?;
+        let known = serde_json::to_value(Config::default())?;
+        for key in table.keys() {
+            if known.get(key).is_none() {
+                problems.push(format!("unknown field '{key}'"));
+            }
+        }
+        if let Some(author) = &config.author {
+            if let Some(problem) = check_author(author) {
+                problems.push(problem);
+            }
+        }
+        if config.data_directory.is_some() {
+            let dir = config.data_dir()?;
+            if let Some(problem) = check_data_dir(&dir) {
+                problems.push(problem);
+            }
+        }
+        if let Some(proxy) = &config.proxy {
+            if !["http", "https"].contains(&proxy.scheme()) {
+                problems.push(format!(
+                    "proxy '{proxy}' uses the unsupported scheme '{}' - use http or https",
+                    proxy.scheme()
+                ));
+            } else if proxy.host().is_none() {
+                problems.push(format!("proxy '{proxy}' has no host"));
+            }
+        }
+        for (idx, name) in config.dev_nodes.iter().enumerate() {
+            if name.trim().is_empty() {
+                problems.push("dev-nodes contains an empty link name".to_string());
+            } else if config.dev_nodes[..idx].contains(name) {
+                problems.push(format!("dev-nodes contains '{name}' more than once"));
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Checks that the author has a name and a plausible email ( if any )
+    fn check_author(author: &Author) -> Option<String> {
+        if author.name.contains(['<', '>']) {
+            return Some(format!(
+                "author '{author}' must be either \"Name <email>\" or just \"Name\""
+            ));
+        }
+        let email = author.email.as_deref()?;
+        let valid = match email.split_once('@') {
+            Some((user, domain)) => {
+                !user.is_empty() && domain.contains('.') && !email.contains(char::is_whitespace)
+            }
+            None => false,
+        };
+        (!valid).then(|| format!("author '{author}' has an invalid email '{email}'"))
+    }
+
+    /// Checks that the data directory either exists or can be created
+    fn check_data_dir(dir: &Path) -> Option<String> {
+        if dir.exists() {
+            return (!dir.is_dir()).then(|| {
+                format!(
+                    "data-directory '{}' exists, but is not a directory",
+                    dir.display()
+                )
+            });
+        }
+        // The directory is created on demand, so the closest existing parent has to be a writable directory
+        let parent = dir.ancestors().skip(1).find(|p| p.exists())?;
+        match fs::metadata(parent) {
+            Ok(meta) if meta.is_dir() && !meta.permissions().readonly() => None,
+            _ => Some(format!(
+                "data-directory '{}' cannot be created below '{}'",
+                dir.display(),
+                parent.display()
+            )),
+        }
+    }
+
+    /// Describes an error in a config file, including the position of the offending value
+    fn describe_parse_error(file: &Path, content: &str, err: &toml::de::Error) -> String {
+        let position = err.span().map(|span| {
+            let before = &content[..span.start.min(content.len())];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |idx| idx + 1) + 1;
+            format!(" at line {line}, column {column}")
+        });
+        format!(
+            "invalid config '{}'{}: {}",
+            file.display(),
+            position.unwrap_or_default(),
+            err.message()
+        )
+    }
+
+    /// Writes the config to disk and returns the path of the config file
+    ///
+    /// If a config file already exists, it is backed up to `config.toml.bak` before overwriting it.
+    /// Overwriting has to be confirmed by the user, unless `assume_yes` is set.
+    pub fn write_config(config: &Config, assume_yes: bool) -> Result<PathBuf> {
+        let config_file_path = get_config_file_path()
+            .context("failed to get config directory - consider to set XDG_CONFIG_HOME.")?;
+
+        if config_file_path.exists() {
+            if !assume_yes
+                && !confirm(format!(
+                    "This will overwrite the existing config at '{}'",
+                    config_file_path.display()
+                ))
+                .interact()?
+            {
+                bail!("Process aborted by user.");
+            }
+            let backup = config_file_path.with_extension("toml.bak");
+            fs::copy(&config_file_path, &backup)
+                .with_context(|| format!("failed to create backup '{}'", backup.display()))?;
+            info(format!(
+                "Saved backup of the old config to '{}'",
+                backup.display()
+            ))?;
+        } else if let Some(parent) = config_file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let config_content = toml::to_string_pretty(config)?;
+        fs::write(&config_file_path, config_content)?;
+        Ok(config_file_path)
+    }
+
     /// Returns a reference to the current config object
     pub fn get_config() -> &'static Config {
         CONFIG.get().expect("config has not been initialized")
     }
 
-    fn config_file() -> Option<PathBuf> {
+    /// Returns the path of the config file, if it exists
+    pub fn config_file() -> Option<PathBuf> {
         let config_file = get_config_file_path()?;
 
         // check if config file exists
@@ -258,4 +1097,87 @@ mod config {
 
         None
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn config_parse_errors() {
+            let content = "author = \"John Doe <john@example.com>\"\ndev-nodes = 42\n";
+            let err = toml::from_str::<Config>(content).unwrap_err();
+            let msg = describe_parse_error(Path::new("config.toml"), content, &err);
+            assert!(
+                msg.starts_with("invalid config 'config.toml' at line 2, column 13: "),
+                "{msg}"
+            );
+        }
+
+        #[test]
+        fn config_validation() -> Result<()> {
+            let dir = crate::util::TestDir::new("config");
+            let file = dir.join(CONFIG_FILE_NAME);
+            fs::write(
+                &file,
+                "confirm-creation = false\nauthor = \"CI Bot\"\ndata-directory = \"data\"\ndev-nodes = [ \"local\" ]\n",
+            )?;
+            let problems = validate_config(&file)?;
+            assert!(problems.is_empty(), "{problems:?}");
+
+            // All problems are reported at once
+            fs::write(dir.join("not-a-dir"), "")?;
+            fs::write(
+                &file,
+                "confirm-creation = false\nauthor = \"John <john>\"\ndata-directory = \"not-a-dir\"\nproxy = \"ftp://proxy\"\ndev_nodes = []\n",
+            )?;
+            let problems = validate_config(&file)?;
+            assert_eq!(problems.len(), 4, "{problems:?}");
+            assert!(problems[0].contains("unknown field 'dev_nodes'"));
+            assert!(problems[1].contains("invalid email 'john'"));
+            assert!(problems[2].contains("not a directory"));
+            assert!(problems[3].contains("unsupported scheme 'ftp'"));
+
+            fs::write(&file, "dev-nodes = 42\n")?;
+            let problems = validate_config(&file)?;
+            assert!(problems[0].contains("at line 1"), "{problems:?}");
+            Ok(())
+        }
+
+        #[test]
+        fn legacy_profile_files() -> Result<()> {
+            let data_dir = crate::util::TestDir::new("legacy");
+            let profile_dir = data_dir.join("profiles").join(DEFAULT_PROFILE);
+            fs::write(data_dir.join(LINK_DB_FILE), "")?;
+
+            // The legacy file is only used by the default profile before the migration
+            let file = locate_profile_file(&data_dir, &profile_dir, true, LINK_DB_FILE);
+            assert_eq!(file, data_dir.join(LINK_DB_FILE));
+            let file = locate_profile_file(&data_dir, &profile_dir, false, LINK_DB_FILE);
+            assert_eq!(file, profile_dir.join(LINK_DB_FILE));
+
+            fs::create_dir_all(&profile_dir)?;
+            let file = locate_profile_file(&data_dir, &profile_dir, true, LINK_DB_FILE);
+            assert_eq!(file, profile_dir.join(LINK_DB_FILE));
+            Ok(())
+        }
+
+        #[test]
+        fn data_directory_resolution() {
+            let base = Path::new("/home/user/.config/borderless-cli");
+            assert_eq!(
+                resolve_path(Path::new("data"), Some(base)),
+                base.join("data")
+            );
+            assert_eq!(
+                resolve_path(Path::new("/var/lib/borderless"), Some(base)),
+                Path::new("/var/lib/borderless")
+            );
+            if let Some(home) = env_path("HOME") {
+                assert_eq!(
+                    resolve_path(Path::new("~/borderless"), Some(base)),
+                    home.join("borderless")
+                );
+            }
+        }
+    }
 }
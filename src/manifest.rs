@@ -0,0 +1,290 @@
+//! Parsing of the package manifest
+//!
+//! The manifest is either a `Manifest.toml` or the `[package.metadata.borderless]` section of the `Cargo.toml`.
+use anyhow::{bail, Context, Result};
+use borderless_pkg::{Capabilities, PkgMeta};
+use serde::Serialize;
+
+use crate::{error::CliError, template::Manifest};
+
+/// Checks that the crate is compiled as `cdylib`
+///
+/// Other crate types compile just fine, but don't produce a wasm module.
+pub fn check_crate_type(cargo_toml: &str) -> Result<()> {
+    let value: toml::Table = toml::from_str(cargo_toml)?;
+    let is_cdylib = value
+        .get("lib")
+        .and_then(|lib| lib.get("crate-type"))
+        .and_then(toml::Value::as_array)
+        .is_some_and(|types| types.iter().any(|t| t.as_str() == Some("cdylib")));
+    if !is_cdylib {
+        bail!(CliError::usage(
+            "crate does not build a wasm module - add the following to your Cargo.toml:\n\n[lib]\ncrate-type = [\"cdylib\"]"
+        ));
+    }
+    Ok(())
+}
+
+/// Parses the content of a `Manifest.toml`
+///
+/// Unknown sections and fields are rejected, so typos don't silently drop a setting.
+pub fn parse_manifest(content: &str) -> Result<Manifest> {
+    check_field_names(&toml::from_str(content)?)?;
+    // Deserialize from the text again, so errors point to the line of the offending field
+    toml::from_str(content).map_err(|e| CliError::usage(e.to_string().trim_end()).into())
+}
+
+fn parse_manifest_table(value: toml::Table) -> Result<Manifest> {
+    check_field_names(&value)?;
+    value
+        .try_into()
+        .map_err(|e: toml::de::Error| CliError::usage(e.message()).into())
+}
+
+/// Checks the fields of the sections, whose types are not under our control
+fn check_field_names(value: &toml::Table) -> Result<()> {
+    if let Some(capabilities) = value.get("capabilities").and_then(toml::Value::as_table) {
+        check_capability_names(capabilities.keys())?;
+    }
+    if let Some(meta) = value.get("meta").and_then(toml::Value::as_table) {
+        let known = known_fields(PkgMeta::default())?;
+        if let Some(unknown) = meta.keys().find(|k| !known.contains(k)) {
+            bail!(CliError::usage(format!(
+                "unknown field '{unknown}' in [meta] - valid fields are: {}",
+                known.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the names of all fields of a struct
+fn known_fields(value: impl Serialize) -> Result<Vec<String>> {
+    let value = serde_json::to_value(value)?;
+    Ok(value
+        .as_object()
+        .into_iter()
+        .flat_map(|o| o.keys().cloned())
+        .collect())
+}
+
+/// Returns the `[package.metadata.borderless]` section of a `Cargo.toml`
+pub fn cargo_metadata(cargo_toml: &toml::Table) -> Option<&toml::Table> {
+    cargo_toml
+        .get("package")?
+        .get("metadata")?
+        .get("borderless")?
+        .as_table()
+}
+
+/// Returns the crate name from the `Cargo.toml`, if it differs from the package name in the manifest
+///
+/// Cargo names the wasm binary after the crate, where dashes and underscores are interchangeable.
+pub fn diverging_crate_name(cargo_toml: &toml::Table, manifest: &Manifest) -> Option<String> {
+    let crate_name = cargo_toml.get("package")?.get("name")?.as_str()?;
+    let pkg_name = &manifest.agent.as_ref().or(manifest.contract.as_ref())?.name;
+    if crate_name.replace('-', "_") == pkg_name.replace('-', "_") {
+        return None;
+    }
+    Some(crate_name.to_string())
+}
+
+/// Fields of `[package.metadata.borderless]`
+pub const METADATA_FIELDS: [&str; 6] = [
+    "type",
+    "app_name",
+    "app_module",
+    "capabilities",
+    "meta",
+    "build",
+];
+
+/// Builds the manifest from the `[package.metadata.borderless]` section
+///
+/// The section has the same fields as the `Manifest.toml`, except that the name is taken from the
+/// `[package]` and the type of the package is set via `type = "contract"` or `type = "agent"`.
+pub fn manifest_from_metadata(
+    cargo_toml: &toml::Table,
+    mut metadata: toml::Table,
+) -> Result<Manifest> {
+    if let Some(unknown) = metadata
+        .keys()
+        .find(|k| !METADATA_FIELDS.contains(&k.as_str()))
+    {
+        bail!(CliError::usage(format!(
+            "unknown field '{unknown}' - valid fields are: {}",
+            METADATA_FIELDS.join(", ")
+        )));
+    }
+    let name = cargo_toml
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .cloned()
+        .context("missing package name")?;
+    let section = match metadata
+        .remove("type")
+        .as_ref()
+        .and_then(toml::Value::as_str)
+    {
+        Some("contract") => "contract",
+        Some("agent") => "agent",
+        _ => bail!(CliError::usage(
+            "type must be set to either \"contract\" or \"agent\""
+        )),
+    };
+
+    let mut info = toml::Table::new();
+    info.insert("name".to_string(), name);
+    for field in ["app_name", "app_module"] {
+        if let Some(value) = metadata.remove(field) {
+            info.insert(field.to_string(), value);
+        }
+    }
+    // What remains are the capabilities, the meta and the build section
+    metadata.insert(section.to_string(), toml::Value::Table(info));
+    parse_manifest_table(metadata)
+}
+
+/// Checks that all declared capabilities are known to [`Capabilities`]
+///
+/// Serde silently ignores unknown fields, so a typo would otherwise just drop the capability.
+fn check_capability_names<'a>(names: impl IntoIterator<Item = &'a String>) -> Result<()> {
+    let known = known_fields(Capabilities {
+        network: false,
+        websocket: false,
+        url_whitelist: Vec::new(),
+    })?;
+    for name in names {
+        if !known.contains(name) {
+            bail!(CliError::usage(format!(
+                "unknown capability '{name}' - valid capabilities are: {}",
+                known.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn capability_names() {
+        let names = ["network".to_string(), "url_whitelist".to_string()];
+        assert!(check_capability_names(&names).is_ok());
+
+        let names = ["htttp".to_string()];
+        let err = check_capability_names(&names).unwrap_err().to_string();
+        assert!(err.contains("'htttp'"), "{err}");
+        assert!(err.contains("network, url_whitelist, websocket"), "{err}");
+    }
+
+    #[test]
+    fn unknown_manifest_fields() {
+        let valid = "[contract]\nname = \"my-contract\"\n\n[meta]\nlicense = \"MIT\"\n";
+        assert!(parse_manifest(valid).is_ok());
+
+        for (manifest, expected) in [
+            (
+                "[contract]\nname = \"x\"\n[capabilites]\nnetwork = true",
+                "capabilites",
+            ),
+            ("[contract]\nname = \"x\"\napp_modul = \"y\"", "app_modul"),
+            (
+                "[contract]\nname = \"x\"\n[meta]\nlicence = \"MIT\"",
+                "licence",
+            ),
+        ] {
+            let err = parse_manifest(manifest).unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+            assert_eq!(ErrorKind::of(&err), ErrorKind::Usage);
+        }
+        // The position of the unknown field is reported
+        let err = parse_manifest("[contract]\nname = \"x\"\napp_modul = \"y\"").unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{err}");
+    }
+
+    #[test]
+    fn build_features() -> Result<()> {
+        let manifest = parse_manifest("[contract]\nname = \"x\"")?;
+        assert!(manifest.build.is_none());
+        let manifest =
+            parse_manifest("[contract]\nname = \"x\"\n[build]\nfeatures = [\"logging\"]")?;
+        assert_eq!(manifest.build.unwrap().features, ["logging"]);
+
+        let err = parse_manifest("[contract]\nname = \"x\"\n[build]\nfeature = [\"logging\"]")
+            .unwrap_err();
+        assert!(err.to_string().contains("feature"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn missing_package_name() {
+        let err = parse_manifest("[agent]\napp_name = \"app\"").unwrap_err();
+        assert!(err.to_string().contains("missing field `name`"), "{err}");
+    }
+
+    #[test]
+    fn manifest_in_cargo_toml() -> Result<()> {
+        let cargo_toml: toml::Table = toml::from_str(
+            r#"
+            [package]
+            name = "my-agent"
+            version = "0.1.0"
+
+            [package.metadata.borderless]
+            type = "agent"
+            app_module = "module"
+
+            [package.metadata.borderless.capabilities]
+            network = true
+            websocket = false
+            url_whitelist = []
+
+            [package.metadata.borderless.meta]
+            license = "MIT"
+            "#,
+        )?;
+        let metadata = cargo_metadata(&cargo_toml).unwrap().clone();
+        let manifest = manifest_from_metadata(&cargo_toml, metadata.clone())?;
+        let agent = manifest.agent.unwrap();
+        assert_eq!(agent.name, "my-agent");
+        assert_eq!(agent.app_module.as_deref(), Some("module"));
+        assert!(manifest.capabilities.unwrap().network);
+        assert_eq!(manifest.meta.unwrap().license.as_deref(), Some("MIT"));
+
+        let mut unknown = metadata.clone();
+        unknown.insert("nmae".to_string(), toml::Value::from("x"));
+        let err = manifest_from_metadata(&cargo_toml, unknown).unwrap_err();
+        assert!(err.to_string().contains("unknown field 'nmae'"), "{err}");
+
+        let mut untyped = metadata;
+        untyped.remove("type");
+        assert!(manifest_from_metadata(&cargo_toml, untyped).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn crate_name_mismatch() -> Result<()> {
+        let cargo_toml: toml::Table = toml::from_str("[package]\nname = \"my_contract\"")?;
+        let manifest = parse_manifest("[contract]\nname = \"my-contract\"")?;
+        assert_eq!(diverging_crate_name(&cargo_toml, &manifest), None);
+        let manifest = parse_manifest("[contract]\nname = \"other-contract\"")?;
+        assert_eq!(
+            diverging_crate_name(&cargo_toml, &manifest).as_deref(),
+            Some("my_contract")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn crate_type() {
+        assert!(check_crate_type("[lib]\ncrate-type = [\"cdylib\", \"rlib\"]").is_ok());
+        for cargo_toml in ["[package]\nname = \"x\"", "[lib]\ncrate-type = [\"rlib\"]"] {
+            let err = check_crate_type(cargo_toml).unwrap_err().to_string();
+            assert!(err.contains("crate-type = [\"cdylib\"]"), "{err}");
+        }
+    }
+}
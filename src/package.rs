@@ -0,0 +1,323 @@
+//! On-disk encodings and validation of package files
+use anyhow::{bail, Context, Result};
+use borderless_hash::Hash256;
+use borderless_pkg::{
+    git_info::GitInfo, semver::SemVer, Registry, Source, SourceType, WasmPkg, WasmPkgNoSource,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{error::CliError, wasm::WASM_MAGIC};
+
+/// Encoding of a package file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PkgFormat {
+    /// Human readable json
+    #[default]
+    Json,
+    /// Compact binary encoding ( postcard )
+    Postcard,
+}
+
+impl PkgFormat {
+    /// Default name of a package file in this encoding
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            PkgFormat::Json => "package.json",
+            PkgFormat::Postcard => "package.postcard",
+        }
+    }
+
+    /// Detects the encoding of a package file from its content
+    ///
+    /// Json packages are always objects, while the postcard encoding starts with the length of the package name.
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => PkgFormat::Json,
+            _ => PkgFormat::Postcard,
+        }
+    }
+
+    /// Encodes a package; json is pretty-printed, so changes of the package can be reviewed
+    pub fn encode(&self, pkg: WasmPkg) -> Result<Vec<u8>> {
+        match self {
+            PkgFormat::Json => {
+                let mut encoded = serde_json::to_vec_pretty(&pkg.into_dto())?;
+                encoded.push(b'\n');
+                Ok(encoded)
+            }
+            PkgFormat::Postcard => {
+                let (def, source) = pkg.into_def_and_source();
+                let encoded = postcard::to_allocvec(&(def, FlatSource::from(source)))?;
+                Ok(encoded)
+            }
+        }
+    }
+
+    /// Same as [`PkgFormat::encode`], but json is written without any whitespace
+    pub fn encode_minified(&self, pkg: WasmPkg) -> Result<Vec<u8>> {
+        match self {
+            PkgFormat::Json => Ok(serde_json::to_vec(&pkg.into_dto())?),
+            PkgFormat::Postcard => self.encode(pkg),
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<WasmPkg> {
+        match self {
+            PkgFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            PkgFormat::Postcard => {
+                let (def, source): (WasmPkgNoSource, FlatSource) = postcard::from_bytes(bytes)?;
+                Ok(WasmPkg::from_def_and_source(def, source.try_into()?))
+            }
+        }
+    }
+}
+
+/// Path, that stands for stdin ( e.g. `borderless pack --stdout | borderless merge introduction.json -` )
+pub const STDIN_PATH: &str = "-";
+
+/// Reads a package file in any of the supported encodings
+///
+/// The path [`STDIN_PATH`] reads the package from stdin; the encoding is detected from the content as well.
+pub fn read_package(path: &Path) -> Result<WasmPkg> {
+    let (bytes, origin) = if path == Path::new(STDIN_PATH) {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .context("failed to read package from stdin")?;
+        (bytes, "stdin".to_string())
+    } else {
+        let bytes =
+            fs::read(path).with_context(|| format!("failed to open '{}'", path.display()))?;
+        (bytes, format!("'{}'", path.display()))
+    };
+    PkgFormat::detect(&bytes)
+        .decode(&bytes)
+        .with_context(|| format!("failed to parse {origin}"))
+}
+
+/// Sets ( or replaces ) the package of an introduction
+pub fn merge_package(introduction: &mut Value, package: WasmPkg) -> Result<()> {
+    let Value::Object(map) = introduction else {
+        bail!("introduction must be a json-object");
+    };
+    map.insert("package".to_string(), serde_json::to_value(package)?);
+    Ok(())
+}
+
+/// Checks that a package is structurally sound
+///
+/// For packages that embed their wasm module, the digest and the wasm header are verified.
+pub fn validate_package(pkg: &WasmPkg) -> Result<()> {
+    if pkg.name.trim().is_empty() {
+        bail!(CliError::usage("invalid package - name is empty"));
+    }
+    match &pkg.source.code {
+        SourceType::Wasm { wasm, .. } => {
+            if !wasm.starts_with(&WASM_MAGIC) {
+                bail!(CliError::usage(format!(
+                    "invalid package '{}' - embedded code is not a wasm module",
+                    pkg.name
+                )));
+            }
+            let digest = Hash256::digest(wasm);
+            if digest != pkg.source.digest {
+                bail!(CliError::usage(format!(
+                    "invalid package '{}' - digest mismatch: expected {}, got {}",
+                    pkg.name,
+                    String::from(pkg.source.digest),
+                    String::from(digest)
+                )));
+            }
+        }
+        // The code is fetched by the node, so there is nothing to check locally
+        SourceType::Registry { .. } => (),
+    }
+    Ok(())
+}
+
+/// Checks that the digest of a package matches the digest given by `--expected-digest`
+///
+/// The expected digest must be the full hex encoded digest; the comparison ignores the case.
+pub fn check_digest(pkg: &WasmPkg, expected: &str) -> Result<()> {
+    let expected = expected.trim().to_ascii_lowercase();
+    if expected.len() != 64 || !expected.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!(CliError::usage(format!(
+            "invalid expected digest '{expected}' - must be 64 hex characters"
+        )));
+    }
+    let actual = String::from(pkg.source.digest);
+    if actual != expected {
+        bail!(CliError::usage(format!(
+            "digest of package '{}' does not match: expected {expected}, got {actual}",
+            pkg.name
+        )));
+    }
+    Ok(())
+}
+
+/// Postcard friendly version of [`Source`]
+///
+/// Postcard supports neither flattening nor untagged enums. This has the same layout as
+/// [`borderless_pkg::SourceFlattened`], but can be converted back without panicking on invalid input.
+#[derive(Serialize, Deserialize)]
+struct FlatSource {
+    version: SemVer,
+    digest: Hash256,
+    registry: Option<Registry>,
+    wasm: Option<Vec<u8>>,
+    git_info: Option<GitInfo>,
+}
+
+impl From<Source> for FlatSource {
+    fn from(source: Source) -> Self {
+        let (registry, wasm, git_info) = match source.code {
+            SourceType::Registry { registry } => (Some(registry), None, None),
+            SourceType::Wasm { wasm, git_info } => (None, Some(wasm), git_info),
+        };
+        FlatSource {
+            version: source.version,
+            digest: source.digest,
+            registry,
+            wasm,
+            git_info,
+        }
+    }
+}
+
+impl TryFrom<FlatSource> for Source {
+    type Error = CliError;
+
+    fn try_from(flat: FlatSource) -> Result<Self, Self::Error> {
+        let code = match (flat.registry, flat.wasm) {
+            (Some(registry), None) => SourceType::Registry { registry },
+            (None, Some(wasm)) => SourceType::Wasm {
+                wasm,
+                git_info: flat.git_info,
+            },
+            _ => {
+                return Err(CliError::usage(
+                    "invalid package source - either a registry or the wasm code must be set",
+                ))
+            }
+        };
+        Ok(Source {
+            version: flat.version,
+            digest: flat.digest,
+            code,
+        })
+    }
+}
+
+/// Fixtures that are shared by the unit tests and the tests in `tests/`
+#[doc(hidden)]
+pub mod test_support {
+    use super::*;
+    use borderless_pkg::PkgType;
+
+    /// A contract package with an empty wasm module and a valid digest
+    pub fn package() -> WasmPkg {
+        let wasm = WASM_MAGIC.to_vec();
+        WasmPkg {
+            name: "some-name".to_string(),
+            app_name: None,
+            app_module: Some("module".to_string()),
+            capabilities: None,
+            pkg_type: PkgType::Contract,
+            meta: Default::default(),
+            source: Source {
+                version: "0.1.0".parse().unwrap(),
+                digest: Hash256::digest(&wasm),
+                code: SourceType::Wasm {
+                    wasm,
+                    git_info: None,
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::package;
+    use super::*;
+
+    #[test]
+    fn round_trip_all_formats() -> Result<()> {
+        for format in [PkgFormat::Json, PkgFormat::Postcard] {
+            let encoded = format.encode(package())?;
+            assert_eq!(PkgFormat::detect(&encoded), format);
+            let decoded = format.decode(&encoded)?;
+            assert_eq!(decoded.name, "some-name");
+            assert_eq!(decoded.app_module.as_deref(), Some("module"));
+            assert_eq!(decoded.source.digest, package().source.digest);
+
+            let minified = format.encode_minified(package())?;
+            assert_eq!(format.decode(&minified)?.name, "some-name");
+        }
+        let pretty = PkgFormat::Json.encode(package())?;
+        let minified = PkgFormat::Json.encode_minified(package())?;
+        assert!(pretty.len() > minified.len());
+        assert!(pretty.ends_with(b"}\n"));
+        assert!(!minified.contains(&b'\n'));
+        Ok(())
+    }
+
+    #[test]
+    fn postcard_layout_matches_flattened_source() -> Result<()> {
+        let (def, source) = package().into_def_and_source();
+        let ours = postcard::to_allocvec(&(&def, FlatSource::from(source.clone())))?;
+        let upstream = postcard::to_allocvec(&(&def, source.flatten()))?;
+        assert_eq!(ours, upstream);
+        Ok(())
+    }
+
+    #[test]
+    fn package_validation() -> Result<()> {
+        let wasm = WASM_MAGIC.to_vec();
+        let mut pkg = WasmPkg {
+            name: "my-contract".to_string(),
+            app_name: None,
+            app_module: None,
+            capabilities: None,
+            pkg_type: borderless_pkg::PkgType::Contract,
+            meta: Default::default(),
+            source: borderless_pkg::Source {
+                version: "0.1.0".parse().map_err(anyhow::Error::msg)?,
+                digest: Hash256::digest(&wasm),
+                code: SourceType::Wasm {
+                    wasm,
+                    git_info: None,
+                },
+            },
+        };
+        assert!(validate_package(&pkg).is_ok());
+
+        // The expected digest is compared case-insensitively against the full digest
+        let digest = String::from(pkg.source.digest);
+        assert!(check_digest(&pkg, &digest.to_ascii_uppercase()).is_ok());
+        let err = check_digest(&pkg, &digest[..8]).unwrap_err();
+        assert!(err.to_string().contains("64 hex characters"), "{err}");
+        let err = check_digest(&pkg, &String::from(Hash256::zero())).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{err}");
+
+        pkg.source.digest = Hash256::digest(b"something else");
+        let err = validate_package(&pkg).unwrap_err();
+        assert!(err.to_string().contains("digest mismatch"), "{err}");
+
+        pkg.source.code = SourceType::Wasm {
+            wasm: b"not wasm".to_vec(),
+            git_info: None,
+        };
+        let err = validate_package(&pkg).unwrap_err();
+        assert!(err.to_string().contains("not a wasm module"), "{err}");
+        Ok(())
+    }
+}
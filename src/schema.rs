@@ -0,0 +1,467 @@
+//! Validation of json documents against a JSON Schema
+//!
+//! Only the structural subset of JSON Schema is supported, which is enough to describe the shape of an introduction:
+//! `type`, `enum`, `const`, `required`, `properties`, `additionalProperties`, `items`, the length- and range-keywords,
+//! `allOf`, `anyOf`, `oneOf`, `not` and local `$ref`s ( like `#/$defs/participant` ).
+//! Annotations ( `title`, `description`, `format` etc. ) are ignored; all other keywords are reported
+//! by [`unsupported_keywords`], so callers can tell the user which parts of the schema are not checked.
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::error::CliError;
+
+/// Keywords, that are checked by [`validate`]
+const SUPPORTED: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "required",
+    "properties",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "minLength",
+    "maxLength",
+    "minProperties",
+    "maxProperties",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+    "$ref",
+];
+
+/// Keywords, that carry no constraints
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "$defs",
+    "definitions",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "format",
+    "readOnly",
+    "writeOnly",
+    "deprecated",
+];
+
+/// A part of a document, that does not match the schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// JSON pointer to the offending value ( empty for the document itself )
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "(root)"
+        } else {
+            &self.pointer
+        };
+        write!(f, "{pointer}: {}", self.message)
+    }
+}
+
+/// Validates a document and returns all violations
+///
+/// Fails, if the schema itself is invalid ( e.g. a `$ref` that points nowhere ).
+pub fn validate(schema: &Value, doc: &Value) -> Result<Vec<Violation>> {
+    let mut validator = Validator {
+        root: schema,
+        violations: Vec::new(),
+    };
+    validator.check(schema, doc, String::new(), 0)?;
+    Ok(validator.violations)
+}
+
+/// Returns the keywords of a schema, that are neither checked nor pure annotations
+pub fn unsupported_keywords(schema: &Value) -> Vec<String> {
+    let mut out = Vec::new();
+    collect_unsupported(schema, &mut out);
+    out.sort();
+    out.dedup();
+    out
+}
+
+fn collect_unsupported(schema: &Value, out: &mut Vec<String>) {
+    let Value::Object(map) = schema else {
+        return;
+    };
+    for (key, value) in map {
+        if !SUPPORTED.contains(&key.as_str()) && !ANNOTATIONS.contains(&key.as_str()) {
+            out.push(key.clone());
+        }
+        match key.as_str() {
+            "properties" | "$defs" | "definitions" => {
+                for sub in value.as_object().into_iter().flat_map(Map::values) {
+                    collect_unsupported(sub, out);
+                }
+            }
+            "allOf" | "anyOf" | "oneOf" => {
+                for sub in value.as_array().into_iter().flatten() {
+                    collect_unsupported(sub, out);
+                }
+            }
+            "items" | "additionalProperties" | "not" => collect_unsupported(value, out),
+            _ => (),
+        }
+    }
+}
+
+/// Maximum nesting of schemas, so recursive `$ref`s terminate
+const MAX_DEPTH: usize = 64;
+
+struct Validator<'a> {
+    root: &'a Value,
+    violations: Vec<Violation>,
+}
+
+impl<'a> Validator<'a> {
+    fn violation(&mut self, pointer: &str, message: impl fmt::Display) {
+        self.violations.push(Violation {
+            pointer: pointer.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    /// Checks a document against a sub-schema, without recording any violations
+    fn matches(&self, schema: &'a Value, doc: &Value, depth: usize) -> Result<bool> {
+        let mut inner = Validator {
+            root: self.root,
+            violations: Vec::new(),
+        };
+        inner.check(schema, doc, String::new(), depth)?;
+        Ok(inner.violations.is_empty())
+    }
+
+    fn resolve(&self, reference: &str) -> Result<&'a Value> {
+        let Some(pointer) = reference.strip_prefix('#') else {
+            bail!(CliError::usage(format!(
+                "invalid schema - only local references are supported, got '{reference}'"
+            )));
+        };
+        match self.root.pointer(pointer) {
+            Some(schema) => Ok(schema),
+            None => bail!(CliError::usage(format!(
+                "invalid schema - reference '{reference}' points nowhere"
+            ))),
+        }
+    }
+
+    fn check(
+        &mut self,
+        schema: &'a Value,
+        doc: &Value,
+        pointer: String,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!(CliError::usage(
+                "invalid schema - references are nested too deeply"
+            ));
+        }
+        let map = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => {
+                self.violation(&pointer, "no value is allowed here");
+                return Ok(());
+            }
+            Value::Object(map) => map,
+            _ => bail!(CliError::usage(format!(
+                "invalid schema at '{pointer}' - must be an object or a boolean"
+            ))),
+        };
+
+        if let Some(reference) = map.get("$ref").and_then(Value::as_str) {
+            let target = self.resolve(reference)?;
+            self.check(target, doc, pointer.clone(), depth + 1)?;
+        }
+        if let Some(expected) = map.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.iter().any(|t| has_type(doc, t)) {
+                self.violation(
+                    &pointer,
+                    format!("expected {}, got {}", types.join(" or "), type_name(doc)),
+                );
+                // All other keywords depend on the type, so they would only repeat the error
+                return Ok(());
+            }
+        }
+        if let Some(values) = map.get("enum").and_then(Value::as_array) {
+            if !values.contains(doc) {
+                let allowed: Vec<_> = values.iter().map(Value::to_string).collect();
+                self.violation(&pointer, format!("must be one of {}", allowed.join(", ")));
+            }
+        }
+        if let Some(value) = map.get("const") {
+            if value != doc {
+                self.violation(&pointer, format!("must be {value}"));
+            }
+        }
+
+        match doc {
+            Value::Object(object) => self.check_object(map, object, &pointer, depth)?,
+            Value::Array(items) => self.check_array(map, items, &pointer, depth)?,
+            Value::String(s) => {
+                self.check_size(map, "Length", &pointer, s.chars().count(), "characters")
+            }
+            Value::Number(n) => {
+                let n = n.as_f64().unwrap_or_default();
+                for (keyword, relation) in [
+                    ("minimum", ">="),
+                    ("maximum", "<="),
+                    ("exclusiveMinimum", ">"),
+                    ("exclusiveMaximum", "<"),
+                ] {
+                    let Some(bound) = map.get(keyword).and_then(Value::as_f64) else {
+                        continue;
+                    };
+                    let ok = match relation {
+                        ">=" => n >= bound,
+                        "<=" => n <= bound,
+                        ">" => n > bound,
+                        _ => n < bound,
+                    };
+                    if !ok {
+                        self.violation(&pointer, format!("must be {relation} {bound}"));
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        if let Some(schemas) = map.get("allOf").and_then(Value::as_array) {
+            for sub in schemas {
+                self.check(sub, doc, pointer.clone(), depth + 1)?;
+            }
+        }
+        if let Some(schemas) = map.get("anyOf").and_then(Value::as_array) {
+            let mut any = false;
+            for sub in schemas {
+                any |= self.matches(sub, doc, depth + 1)?;
+            }
+            if !any {
+                self.violation(&pointer, "does not match any of the allowed schemas");
+            }
+        }
+        if let Some(schemas) = map.get("oneOf").and_then(Value::as_array) {
+            let mut count = 0;
+            for sub in schemas {
+                count += usize::from(self.matches(sub, doc, depth + 1)?);
+            }
+            if count != 1 {
+                self.violation(
+                    &pointer,
+                    format!("must match exactly one schema, but matches {count}"),
+                );
+            }
+        }
+        if let Some(sub) = map.get("not") {
+            if self.matches(sub, doc, depth + 1)? {
+                self.violation(&pointer, "must not match the schema in 'not'");
+            }
+        }
+        Ok(())
+    }
+
+    fn check_object(
+        &mut self,
+        map: &'a Map<String, Value>,
+        object: &Map<String, Value>,
+        pointer: &str,
+        depth: usize,
+    ) -> Result<()> {
+        for field in map
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(field) = field.as_str().filter(|f| !object.contains_key(*f)) {
+                self.violation(pointer, format!("missing required field '{field}'"));
+            }
+        }
+        self.check_size(map, "Properties", pointer, object.len(), "fields");
+
+        let properties = map.get("properties").and_then(Value::as_object);
+        for (key, value) in object {
+            let child = format!("{pointer}/{}", escape(key));
+            match properties.and_then(|p| p.get(key)) {
+                Some(sub) => self.check(sub, value, child, depth + 1)?,
+                None => match map.get("additionalProperties") {
+                    Some(Value::Bool(false)) => self.violation(&child, "unknown field"),
+                    Some(sub) => self.check(sub, value, child, depth + 1)?,
+                    None => (),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn check_array(
+        &mut self,
+        map: &'a Map<String, Value>,
+        items: &[Value],
+        pointer: &str,
+        depth: usize,
+    ) -> Result<()> {
+        self.check_size(map, "Items", pointer, items.len(), "items");
+        if map.get("uniqueItems") == Some(&Value::Bool(true)) {
+            for (idx, item) in items.iter().enumerate() {
+                if items[..idx].contains(item) {
+                    self.violation(&format!("{pointer}/{idx}"), "duplicate item");
+                }
+            }
+        }
+        if let Some(sub) = map.get("items") {
+            for (idx, item) in items.iter().enumerate() {
+                self.check(sub, item, format!("{pointer}/{idx}"), depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the `min<suffix>` and `max<suffix>` keywords ( like `minItems` and `maxItems` )
+    fn check_size(
+        &mut self,
+        map: &Map<String, Value>,
+        suffix: &str,
+        pointer: &str,
+        len: usize,
+        unit: &str,
+    ) {
+        let bound = |prefix: &str| {
+            map.get(&format!("{prefix}{suffix}"))
+                .and_then(Value::as_u64)
+        };
+        let len = len as u64;
+        if let Some(min) = bound("min").filter(|min| len < *min) {
+            self.violation(pointer, format!("must have at least {min} {unit}"));
+        }
+        if let Some(max) = bound("max").filter(|max| len > *max) {
+            self.violation(pointer, format!("must have at most {max} {unit}"));
+        }
+    }
+}
+
+fn has_type(doc: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => doc.as_i64().is_some() || doc.as_u64().is_some(),
+        "number" => doc.is_number(),
+        other => type_name(doc) == other,
+    }
+}
+
+fn type_name(doc: &Value) -> &'static str {
+    match doc {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Escapes a field name for a JSON pointer ( RFC 6901 )
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "required": ["id", "participants", "package"],
+            "properties": {
+                "id": { "type": "string", "minLength": 36 },
+                "participants": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": { "$ref": "#/$defs/participant" }
+                },
+                "package": { "type": "object" },
+                "version": { "type": "integer", "minimum": 0 }
+            },
+            "additionalProperties": false,
+            "$defs": {
+                "participant": { "type": "string", "pattern": "^[0-9a-f-]+$" }
+            }
+        })
+    }
+
+    #[test]
+    fn valid_documents() -> Result<()> {
+        let doc = json!({
+            "id": "cfc23cb3-f447-8107-8f93-9bfb8e1d157d",
+            "participants": ["0bc23cb3-f447-8107-8f93-9bfb8e1d157d"],
+            "package": {},
+            "version": 1
+        });
+        assert_eq!(validate(&schema(), &doc)?, []);
+        assert_eq!(unsupported_keywords(&schema()), ["pattern"]);
+        Ok(())
+    }
+
+    #[test]
+    fn violations_have_pointers() -> Result<()> {
+        let doc = json!({
+            "id": "short",
+            "participants": ["ok", 42],
+            "version": -1,
+            "a/b": true
+        });
+        let violations: Vec<String> = validate(&schema(), &doc)?
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            [
+                "(root): missing required field 'package'",
+                "/a~1b: unknown field",
+                "/id: must have at least 36 characters",
+                "/participants/1: expected string, got number",
+                "/version: must be >= 0",
+            ]
+        );
+
+        let err = validate(&json!({ "$ref": "#/$defs/missing" }), &doc).unwrap_err();
+        assert!(err.to_string().contains("points nowhere"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn combinators() -> Result<()> {
+        let schema = json!({ "oneOf": [{ "type": "string" }, { "type": "integer" }] });
+        assert!(validate(&schema, &json!(1))?.is_empty());
+        assert_eq!(validate(&schema, &json!(null))?.len(), 1);
+        let schema =
+            json!({ "anyOf": [{ "const": "a" }, { "enum": ["b", "c"] }], "not": { "const": "c" } });
+        assert!(validate(&schema, &json!("b"))?.is_empty());
+        assert_eq!(validate(&schema, &json!("c"))?.len(), 1);
+        assert_eq!(validate(&schema, &json!("d"))?.len(), 1);
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use borderless_hash::Hash256;
 use borderless_pkg::{Capabilities, PkgMeta, PkgType};
 use convert_case::{Case, Casing};
 use rust_embed::Embed;
@@ -9,25 +10,115 @@ use serde::{Deserialize, Serialize};
 #[folder = "templates/"]
 struct Templates;
 
+/// Number of hex characters of the template digest, that make up its version
+const TEMPLATE_VERSION_LEN: usize = 12;
+
+/// A template, that can be used to initialize a new package
+#[derive(Debug, Clone)]
+pub struct InitTemplate {
+    /// Name of the template ( used by `borderless init --template <name>` )
+    pub name: String,
+    /// Type of the package that is generated from the template
+    pub pkg_type: PkgType,
+    pub label: String,
+    pub hint: String,
+}
+
+impl InitTemplate {
+    /// Returns the version of the template
+    ///
+    /// This is a short digest ( the first 12 hex characters ) over all embedded files,
+    /// that the template consists of. It changes with every modification of the template, so it can be used to tell apart
+    /// projects that were scaffolded by different versions of the cli.
+    pub fn version(&self) -> Result<String> {
+        let files = match self.pkg_type {
+            PkgType::Contract => [
+                "manifest-contract.toml",
+                "init-lib-contract.rs",
+                "init-tests-contract.rs",
+            ],
+            PkgType::Agent => [
+                "manifest-agent.toml",
+                "init-lib-agent.rs",
+                "init-tests-agent.rs",
+            ],
+        };
+        let mut content = Vec::new();
+        for file in files {
+            let data = Templates::get(file).with_context(|| format!("missing template {file}"))?;
+            content.extend_from_slice(&data.data);
+        }
+        let mut version = String::from(Hash256::digest(&content));
+        version.truncate(TEMPLATE_VERSION_LEN);
+        Ok(version)
+    }
+
+    /// Comment, that is written at the top of the generated `Manifest.toml`
+    pub fn manifest_header(&self) -> Result<String> {
+        Ok(format!(
+            "# Generated by borderless-cli {} from template '{}' ( version {} )\n",
+            env!("CARGO_PKG_VERSION"),
+            self.name,
+            self.version()?
+        ))
+    }
+}
+
+/// Returns all templates that are available for `borderless init`
+///
+/// Currently these are only the embedded templates.
+pub fn init_templates() -> Vec<InitTemplate> {
+    vec![
+        InitTemplate {
+            name: "contract".to_string(),
+            pkg_type: PkgType::Contract,
+            label: "Contract 🔗  ".to_string(),
+            hint: "initializes a SmartContract".to_string(),
+        },
+        InitTemplate {
+            name: "agent".to_string(),
+            pkg_type: PkgType::Agent,
+            label: "Agent    🤖✨".to_string(),
+            hint: "initializes a Software-Agent".to_string(),
+        },
+    ]
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Manifest {
     pub agent: Option<PkgInfo>,
     pub contract: Option<PkgInfo>,
     pub capabilities: Option<Capabilities>,
     pub meta: Option<PkgMeta>,
+    pub build: Option<BuildConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PkgInfo {
     pub name: String,
     pub app_name: Option<String>,
     pub app_module: Option<String>,
 }
 
+/// The `[build]` section of the manifest
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+    /// Cargo features, that are enabled for the wasm build ( `borderless pack --features` overrides them )
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+/// Generates the `Manifest.toml` of a new package
+///
+/// The capabilities are only written for agents, since contracts cannot make any network calls.
 pub fn generate_manifest(
     pkg_name: &str,
     pkg_type: &PkgType,
     authors: Vec<String>,
+    capabilities: &Capabilities,
 ) -> Result<String> {
     let manifest_template = match pkg_type {
         PkgType::Contract => Templates::get("manifest-contract.toml"),
@@ -45,11 +136,13 @@ pub fn generate_manifest(
     // Build manifest from template
     let manifest = String::from_utf8(manifest_template)?
         .replace("__NAME__", &name_expr)
-        .replace("__AUTHORS__", &authors_expr);
+        .replace("__AUTHORS__", &authors_expr)
+        .replace("__NETWORK__", &capabilities.network.to_string())
+        .replace("__WEBSOCKET__", &capabilities.websocket.to_string());
     Ok(manifest)
 }
 
-pub fn generate_lib_rs(pkg_name: &str, pkg_type: &PkgType) -> Result<String> {
+pub fn generate_lib_rs(pkg_name: &str, pkg_type: &PkgType, with_tests: bool) -> Result<String> {
     let lib_template = match pkg_type {
         PkgType::Contract => Templates::get("init-lib-contract.rs"),
         PkgType::Agent => Templates::get("init-lib-agent.rs"),
@@ -58,40 +151,223 @@ pub fn generate_lib_rs(pkg_name: &str, pkg_type: &PkgType) -> Result<String> {
     .data
     .to_vec();
 
+    // The test module is inserted at the end of the contract or agent module
+    let tests = if with_tests {
+        let tests_template = match pkg_type {
+            PkgType::Contract => Templates::get("init-tests-contract.rs"),
+            PkgType::Agent => Templates::get("init-tests-agent.rs"),
+        }
+        .context("missing tests template")?
+        .data
+        .to_vec();
+        String::from_utf8(tests_template)?
+    } else {
+        String::new()
+    };
+
     let module_name = pkg_name.to_case(Case::Snake);
     let state_name = pkg_name.to_case(Case::Pascal);
 
     let lib = String::from_utf8(lib_template)?
+        .replace("__TESTS__", &tests)
         .replace("__module_name__", &module_name)
         .replace("__StateName__", &state_name);
     Ok(lib)
 }
 
+/// Returns the JSON schema of the `package.json`
+pub fn package_schema() -> Result<String> {
+    let schema = Templates::get("package.schema.json")
+        .context("missing package schema")?
+        .data
+        .to_vec();
+    Ok(String::from_utf8(schema)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn capabilities(network: bool, websocket: bool) -> Capabilities {
+        Capabilities {
+            network,
+            websocket,
+            url_whitelist: Vec::new(),
+        }
+    }
+
     #[test]
     fn agent_manifest_template() -> Result<()> {
-        let manifest_str = generate_manifest("some-name", &PkgType::Agent, vec![])?;
+        let manifest_str = generate_manifest(
+            "some-name",
+            &PkgType::Agent,
+            vec![],
+            &capabilities(true, false),
+        )?;
         // Try parse that
         let manifest: Manifest = toml::from_str(&manifest_str)?;
         assert!(manifest.agent.is_some());
         assert!(manifest.contract.is_none());
         let agent = manifest.agent.unwrap();
         assert_eq!(agent.name, "some-name");
+        let capabilities = manifest.capabilities.unwrap();
+        assert!(capabilities.network);
+        assert!(!capabilities.websocket);
         Ok(())
     }
 
     #[test]
     fn contract_manifest_template() -> Result<()> {
-        let manifest_str = generate_manifest("some-name", &PkgType::Contract, vec![])?;
+        let manifest_str = generate_manifest(
+            "some-name",
+            &PkgType::Contract,
+            vec![],
+            &capabilities(true, true),
+        )?;
         // Try parse that
         let manifest: Manifest = toml::from_str(&manifest_str)?;
         assert!(manifest.agent.is_none());
         assert!(manifest.contract.is_some());
+        assert!(manifest.capabilities.is_none());
         let contract = manifest.contract.unwrap();
         assert_eq!(contract.name, "some-name");
         Ok(())
     }
+
+    #[test]
+    fn manifest_template_examples() -> Result<()> {
+        for pkg_type in [PkgType::Contract, PkgType::Agent] {
+            let manifest = generate_manifest(
+                "some-name",
+                &pkg_type,
+                vec!["John Doe <john@example.com>".to_string()],
+                &capabilities(false, false),
+            )?;
+            crate::manifest::parse_manifest(&manifest)?;
+
+            // The commented out examples must be valid fields as well
+            let uncommented: Vec<_> = manifest
+                .lines()
+                .map(|line| match line.strip_prefix('#') {
+                    Some(rest) if rest.starts_with(|c: char| c.is_ascii_lowercase()) => rest,
+                    _ => line,
+                })
+                .collect();
+            let manifest = crate::manifest::parse_manifest(&uncommented.join("\n"))?;
+            let info = manifest.agent.or(manifest.contract).unwrap();
+            assert_eq!(info.app_module.as_deref(), Some("payments"));
+            assert!(manifest.meta.unwrap().license.is_some());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn template_versions() -> Result<()> {
+        let templates = init_templates();
+        let versions: Vec<_> = templates
+            .iter()
+            .map(InitTemplate::version)
+            .collect::<Result<_>>()?;
+        for version in &versions {
+            assert_eq!(version.len(), TEMPLATE_VERSION_LEN);
+            assert!(version.chars().all(|c| c.is_ascii_hexdigit()), "{version}");
+        }
+        assert_ne!(versions[0], versions[1]);
+
+        // The header must be a valid toml comment
+        let header = templates[1].manifest_header()?;
+        let manifest = format!(
+            "{header}{}",
+            generate_manifest(
+                "some-name",
+                &PkgType::Agent,
+                vec![],
+                &capabilities(true, true)
+            )?
+        );
+        assert!(header.contains(&versions[1]));
+        let manifest: Manifest = toml::from_str(&manifest)?;
+        assert!(manifest.agent.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn package_schema_covers_package_fields() -> Result<()> {
+        let schema: serde_json::Value = serde_json::from_str(&package_schema()?)?;
+        let pkg = serde_json::json!({
+            "name": "some-name",
+            "app_name": "app",
+            "app_module": "module",
+            "pkg_type": "agent",
+            "capabilities": { "network": true, "websocket": false, "url_whitelist": [] },
+            "meta": { "authors": [], "license": "MIT" },
+            "source": { "version": "0.1.0", "digest": "00", "wasm": "AGFzbQEAAAA=" },
+        });
+        // Every field of a package must be described by the schema
+        let properties = &schema["properties"];
+        for (key, value) in pkg.as_object().unwrap() {
+            assert!(properties.get(key).is_some(), "missing {key}");
+            if let Some(fields) = value.as_object() {
+                for field in fields.keys() {
+                    assert!(
+                        properties[key]["properties"].get(field).is_some(),
+                        "missing {key}.{field}"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn encoded_packages_match_package_schema() -> Result<()> {
+        use crate::package::test_support;
+        use borderless_pkg::{git_info::GitInfo, SourceType, WasmPkg};
+
+        let schema: serde_json::Value = serde_json::from_str(&package_schema()?)?;
+        for (pkg_type, capabilities) in [
+            (PkgType::Contract, None),
+            (PkgType::Agent, Some(capabilities(true, false))),
+        ] {
+            let mut pkg = WasmPkg {
+                app_name: Some("app".to_string()),
+                app_module: None,
+                capabilities,
+                pkg_type,
+                meta: PkgMeta {
+                    authors: vec!["John Doe <john@example.com>".parse().unwrap()],
+                    license: Some("MIT".to_string()),
+                    ..Default::default()
+                },
+                ..test_support::package()
+            };
+            if let SourceType::Wasm { git_info, .. } = &mut pkg.source.code {
+                *git_info = Some(GitInfo::new(Some("v0.1.0"), Some(2), "abcdef1", true));
+            }
+            let encoded = crate::package::PkgFormat::Json.encode(pkg)?;
+            let mut encoded: serde_json::Value = serde_json::from_slice(&encoded)?;
+            let violations = crate::schema::validate(&schema, &encoded)?;
+            assert!(violations.is_empty(), "{violations:?}");
+
+            // Make sure the schema is actually checked
+            encoded["source"]["digest"] = serde_json::json!(42);
+            assert_eq!(crate::schema::validate(&schema, &encoded)?.len(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn lib_rs_test_module() -> Result<()> {
+        for pkg_type in [PkgType::Contract, PkgType::Agent] {
+            let lib = generate_lib_rs("some-name", &pkg_type, false)?;
+            assert!(!lib.contains("__"));
+            assert!(!lib.contains("#[cfg(test)]"));
+
+            let lib = generate_lib_rs("some-name", &pkg_type, true)?;
+            assert!(!lib.contains("__"));
+            assert!(lib.contains("#[cfg(test)]"));
+            assert!(lib.contains("SomeName { switch"));
+        }
+        Ok(())
+    }
 }
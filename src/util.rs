@@ -0,0 +1,285 @@
+//! Small helpers that are shared between the subcommands
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use std::{
+    fs,
+    io::{self, BufReader, IsTerminal, Write},
+    path::Path,
+};
+
+use crate::{
+    config::get_config, error::CliError, interrupt, log::warning, package::STDIN_PATH, schema,
+};
+
+/// Checks that `path` exists and is a file
+///
+/// `what` describes the file in the error message ( e.g. "introduction" ).
+pub fn ensure_file(path: &Path, what: &str) -> Result<()> {
+    if !path.exists() {
+        bail!(CliError::usage(format!(
+            "failed to read {what} at '{}' - file does not exist",
+            path.display()
+        )));
+    }
+    if !path.is_file() {
+        bail!(CliError::usage(format!("{} is not a file", path.display())));
+    }
+    Ok(())
+}
+
+/// Like [`ensure_file`], but also accepts `-` for stdin
+pub fn ensure_input(path: &Path, what: &str) -> Result<()> {
+    if path == Path::new(STDIN_PATH) {
+        return Ok(());
+    }
+    ensure_file(path, what)
+}
+
+/// Asks the user to confirm the creation or overwrite of files, if `confirm-creation` is set in the config
+///
+/// The confirmation is skipped with `--yes`.
+pub fn confirm_creation(prompt: impl std::fmt::Display, assume_yes: bool) -> Result<()> {
+    if !get_config().confirm_creation || assume_yes {
+        return Ok(());
+    }
+    ensure_interactive("pass --yes to confirm")?;
+    if !cliclack::confirm(prompt).interact()? {
+        bail!("Process aborted by user.");
+    }
+    Ok(())
+}
+
+/// Asks before a file is overwritten, and like [`confirm_creation`] before a new file is created
+///
+/// Both confirmations are skipped with `--yes`.
+pub fn confirm_write(path: &Path, assume_yes: bool) -> Result<()> {
+    if !path.exists() {
+        return confirm_creation(format!("Create '{}'?", path.display()), assume_yes);
+    }
+    if assume_yes {
+        return Ok(());
+    }
+    ensure_interactive(format!("pass --yes to overwrite '{}'", path.display()))?;
+    if !cliclack::confirm(format!(
+        "This will overwrite the existing file at '{}'",
+        path.display()
+    ))
+    .interact()?
+    {
+        bail!("Process aborted by user.");
+    }
+    Ok(())
+}
+
+/// Returns true, if prompts can be answered by the user
+///
+/// cliclack draws its prompts on stderr and reads the keys from stdin, or from `/dev/tty` if stdin is redirected.
+/// Without a terminal on both ends, prompts either hang or fail with an opaque io-error.
+pub fn is_interactive() -> bool {
+    io::stderr().is_terminal() && (io::stdin().is_terminal() || fs::File::open("/dev/tty").is_ok())
+}
+
+/// Fails fast, if a prompt is about to be shown without a terminal
+///
+/// `alternative` tells the user how to provide the answer non-interactively ( e.g. "pass --yes" ).
+pub fn ensure_interactive(alternative: impl std::fmt::Display) -> Result<()> {
+    if !is_interactive() {
+        bail!(CliError::usage(format!(
+            "cannot prompt for input without a terminal - {alternative}"
+        )));
+    }
+    Ok(())
+}
+
+/// Reads and parses a json file with a buffered reader
+///
+/// Errors always contain the path of the file that could not be read or parsed. The path `-` reads from stdin.
+pub fn read_buffered<S: DeserializeOwned>(path: &Path) -> Result<S> {
+    if path == Path::new(STDIN_PATH) {
+        let reader = BufReader::new(io::stdin().lock());
+        return serde_json::from_reader(reader).context("failed to parse json from stdin");
+    }
+    let file =
+        fs::File::open(path).with_context(|| format!("failed to open '{}'", path.display()))?;
+    let reader = BufReader::new(file);
+    let value = serde_json::from_reader(reader)
+        .with_context(|| format!("failed to parse '{}'", path.display()))?;
+    Ok(value)
+}
+
+/// Validates a json document against the JSON Schema in `schema_path`
+///
+/// All violations are reported at once. Keywords of the schema, that cannot be checked, only produce a warning.
+pub fn check_schema(schema_path: &Path, doc: &serde_json::Value, what: &str) -> Result<()> {
+    ensure_file(schema_path, "schema")?;
+    let schema: serde_json::Value = read_buffered(schema_path)?;
+    let unsupported = schema::unsupported_keywords(&schema);
+    if !unsupported.is_empty() {
+        warning(format!(
+            "Ignoring unsupported keywords of schema '{}': {}",
+            schema_path.display(),
+            unsupported.join(", ")
+        ))?;
+    }
+    let violations = schema::validate(&schema, doc)
+        .with_context(|| format!("failed to apply schema '{}'", schema_path.display()))?;
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let violations: Vec<_> = violations.iter().map(ToString::to_string).collect();
+    bail!(CliError::usage(format!(
+        "{what} does not match schema '{}':\n{}",
+        schema_path.display(),
+        violations.join("\n")
+    )))
+}
+
+/// Replaces the content of a file atomically
+///
+/// The content is written to a temporary file in the same directory, which is then renamed into place.
+/// If writing fails or is interrupted ( also by Ctrl-C ), the previous file stays untouched.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let _guard = interrupt::catch();
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("invalid file path '{}'", path.display()))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.tmp-{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    let result = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .and_then(|()| {
+            if interrupt::interrupted() {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            fs::rename(&tmp, path)
+        });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        if e.kind() == io::ErrorKind::Interrupted {
+            bail!(CliError::interrupted(format!(
+                "interrupted - '{}' was not written",
+                path.display()
+            )));
+        }
+        return Err(e).with_context(|| format!("failed to write '{}'", path.display()));
+    }
+    Ok(())
+}
+
+/// Formats rows as a table with left-aligned columns
+pub fn format_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    let header = header.map(str::to_string);
+    for row in std::iter::once(&header).chain(rows) {
+        let line: Vec<_> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Temporary directory for tests, that is removed when it is dropped ( even if the test fails )
+#[cfg(test)]
+pub struct TestDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Creates an empty directory with a name, that is unique within all tests
+    pub fn new(prefix: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!(
+            "borderless-{prefix}-test-{}-{n}",
+            std::process::id()
+        ));
+        // Leftovers of an earlier run with the same pid
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create test directory");
+        TestDir(dir)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_writes() -> Result<()> {
+        let dir = TestDir::new("util");
+        let file = dir.join("package.json");
+        fs::write(&file, "old")?;
+        write_atomic(&file, b"new")?;
+        assert_eq!(fs::read_to_string(&file)?, "new");
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(&dir)?.count(), 1);
+
+        assert!(write_atomic(&dir.join("missing/package.json"), b"new").is_err());
+
+        // A failed write keeps the previous content and removes its temporary file
+        #[cfg(target_os = "linux")]
+        {
+            fs::write(&file, "old")?;
+            // Every write to the temporary file fails with "no space left on device"
+            let tmp = dir.join(format!(".package.json.tmp-{}", std::process::id()));
+            std::os::unix::fs::symlink("/dev/full", &tmp)?;
+            let err = write_atomic(&file, b"new").unwrap_err();
+            assert!(err.to_string().contains("failed to write"), "{err}");
+            assert_eq!(fs::read_to_string(&file)?, "old");
+            assert!(fs::symlink_metadata(&tmp).is_err());
+            assert_eq!(fs::read_dir(&dir)?.count(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn table_layout() {
+        let rows = [
+            ["c-1".to_string(), "contract".to_string()],
+            ["a-22".to_string(), "agent".to_string()],
+        ];
+        assert_eq!(
+            format_table(["ID", "TYPE"], &rows),
+            "ID    TYPE\nc-1   contract\na-22  agent\n"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Helpers to inspect and modify wasm modules
+use anyhow::{bail, Context, Result};
+
+/// The first bytes of every wasm module ( `\0asm` followed by version 1 )
+pub const WASM_MAGIC: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+/// Id of custom sections in the wasm binary format
+const CUSTOM_SECTION_ID: u8 = 0;
+
+/// Returns true for custom sections, that only carry debug- or toolchain-information
+///
+/// The borderless runtime does not rely on any custom section, but unknown sections are kept anyway.
+fn is_non_essential(name: &str) -> bool {
+    matches!(
+        name,
+        "name" | "producers" | "target_features" | "sourceMappingURL" | "external_debug_info"
+    ) || name.starts_with(".debug_")
+}
+
+/// Removes all non-essential custom sections from a wasm module
+pub fn strip_custom_sections(wasm: &[u8]) -> Result<Vec<u8>> {
+    if !wasm.starts_with(&WASM_MAGIC) {
+        bail!("not a wasm module");
+    }
+    let mut out = WASM_MAGIC.to_vec();
+    let mut pos = WASM_MAGIC.len();
+    while pos < wasm.len() {
+        let start = pos;
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_leb128(wasm, &mut pos)? as usize;
+        let end = pos
+            .checked_add(size)
+            .filter(|end| *end <= wasm.len())
+            .context("invalid wasm module - section exceeds module size")?;
+
+        if id == CUSTOM_SECTION_ID {
+            let mut name_pos = pos;
+            let len = read_leb128(wasm, &mut name_pos)? as usize;
+            let name = name_pos
+                .checked_add(len)
+                .filter(|name_end| *name_end <= end)
+                .and_then(|name_end| std::str::from_utf8(&wasm[name_pos..name_end]).ok())
+                .context("invalid wasm module - malformed custom section name")?;
+            if is_non_essential(name) {
+                pos = end;
+                continue;
+            }
+        }
+        out.extend_from_slice(&wasm[start..end]);
+        pos = end;
+    }
+    Ok(out)
+}
+
+/// Reads an unsigned LEB128 encoded integer and advances the position
+fn read_leb128(bytes: &[u8], pos: &mut usize) -> Result<u32> {
+    let mut result: u32 = 0;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes
+            .get(*pos)
+            .context("invalid wasm module - unexpected end of input")?;
+        *pos += 1;
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    bail!("invalid wasm module - integer too large")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom_section(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut payload = vec![name.len() as u8];
+        payload.extend_from_slice(name.as_bytes());
+        payload.extend_from_slice(data);
+        let mut section = vec![CUSTOM_SECTION_ID, payload.len() as u8];
+        section.extend(payload);
+        section
+    }
+
+    #[test]
+    fn strip_debug_sections() -> Result<()> {
+        let type_section = [0x01, 0x01, 0x00];
+        let mut wasm = WASM_MAGIC.to_vec();
+        wasm.extend_from_slice(&type_section);
+        wasm.extend(custom_section("name", b"some names"));
+        wasm.extend(custom_section("custom-data", b"keep me"));
+        wasm.extend(custom_section(".debug_info", b"/home/user/project"));
+
+        let stripped = strip_custom_sections(&wasm)?;
+        let mut expected = WASM_MAGIC.to_vec();
+        expected.extend_from_slice(&type_section);
+        expected.extend(custom_section("custom-data", b"keep me"));
+        assert_eq!(stripped, expected);
+
+        // Truncated modules are rejected
+        assert!(strip_custom_sections(&wasm[..wasm.len() - 1]).is_err());
+        Ok(())
+    }
+}
@@ -42,4 +42,4 @@ pub mod __module_name__ {
             self.flip_switch();
         }
     }
-}
+__TESTS__}
@@ -41,4 +41,4 @@ pub mod __module_name__ {
             self.switch = switch;
         }
     }
-}
+__TESTS__}
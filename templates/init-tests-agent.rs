@@ -0,0 +1,20 @@
+
+    // --- Actions and schedules are regular methods, so they can be tested like any other rust code
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn flip_switch() {
+            let mut state = __StateName__ { switch: false };
+            state.flip_switch();
+            assert!(state.switch);
+        }
+
+        #[test]
+        fn autoflip() {
+            let mut state = __StateName__ { switch: true };
+            state.autoflip();
+            assert!(!state.switch);
+        }
+    }
@@ -0,0 +1,22 @@
+
+    // --- Actions are regular methods, so they can be tested like any other rust code
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn flip_switch() {
+            let mut state = __StateName__ { switch: false };
+            state.flip_switch();
+            assert!(state.switch);
+            state.flip_switch();
+            assert!(!state.switch);
+        }
+
+        #[test]
+        fn set_switch() {
+            let mut state = __StateName__ { switch: false };
+            state.set_switch(true);
+            assert!(state.switch);
+        }
+    }
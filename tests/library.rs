@@ -0,0 +1,46 @@
+//! Uses the library the way an external tool would
+use anyhow::Result;
+use borderless_cli::{
+    manifest::parse_manifest,
+    package::{check_digest, merge_package, test_support, validate_package, PkgFormat},
+    template::generate_manifest,
+    wasm::WASM_MAGIC,
+};
+use borderless_hash::Hash256;
+use borderless_pkg::{Capabilities, PkgType, WasmPkg};
+
+#[test]
+fn validate_and_merge_package() -> Result<()> {
+    let manifest = generate_manifest(
+        "my-agent",
+        &PkgType::Agent,
+        vec!["Jane Doe <jane@example.com>".to_string()],
+        &Capabilities {
+            network: true,
+            websocket: false,
+            url_whitelist: Vec::new(),
+        },
+    )?;
+    let manifest = parse_manifest(&manifest)?;
+    let agent = manifest.agent.unwrap();
+
+    let pkg = WasmPkg {
+        name: agent.name,
+        app_name: agent.app_name,
+        app_module: agent.app_module,
+        capabilities: manifest.capabilities,
+        pkg_type: PkgType::Agent,
+        meta: manifest.meta.unwrap_or_default(),
+        ..test_support::package()
+    };
+
+    let encoded = PkgFormat::Postcard.encode(pkg)?;
+    let pkg = PkgFormat::detect(&encoded).decode(&encoded)?;
+    validate_package(&pkg)?;
+    check_digest(&pkg, &String::from(Hash256::digest(&WASM_MAGIC)))?;
+
+    let mut introduction = serde_json::json!({ "participants": [] });
+    merge_package(&mut introduction, pkg)?;
+    assert_eq!(introduction["package"]["name"], "my-agent");
+    Ok(())
+}